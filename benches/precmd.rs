@@ -0,0 +1,77 @@
+//! Measures the latency `rv precmd` adds to every prompt, once with nothing
+//! changed since the previous prompt and once with the activation recomputed.
+//!
+//! Run with `cargo bench --bench precmd`.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+const RUNS: u32 = 100;
+
+fn precmd(project: &Path, root: &Path, env: &HashMap<String, String>) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rv"))
+        .arg("precmd")
+        .current_dir(project)
+        .env("XDG_DATA_HOME", root.join("data"))
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .env("OLDPWD", project)
+        .envs(env)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Applies the `export` and `unset` lines of the precmd output to `env`.
+fn apply(output: &str, env: &mut HashMap<String, String>) {
+    for line in output.lines() {
+        if let Some((key, value)) = line.strip_prefix("export ").and_then(|l| l.split_once('=')) {
+            env.insert(key.to_string(), value.trim_matches('\'').to_string());
+        } else if let Some(key) = line.strip_prefix("unset ") {
+            env.remove(key);
+        }
+    }
+}
+
+fn measure(name: &str, mut run: impl FnMut()) {
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        run();
+        total += start.elapsed();
+    }
+    println!("{:<12} {:>8.2?} per prompt", name, total / RUNS);
+}
+
+fn main() {
+    let root = std::env::temp_dir().join(format!("rv-bench-{}", std::process::id()));
+    let project = root.join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    std::fs::create_dir_all(root.join("data").join("rv")).unwrap();
+
+    let variables: String = (0..50).map(|i| format!("VAR_{} = \"value {}\"\n", i, i)).collect();
+    std::fs::write(project.join("rv.toml"), format!("[dev]\n{}", variables)).unwrap();
+    let metadata = serde_json::json!({
+        project.to_str().unwrap(): { "name": "dev", "variables": null },
+    });
+    std::fs::write(root.join("data").join("rv").join("metadata.json"), metadata.to_string()).unwrap();
+
+    let mut env = HashMap::from([("RV_CHECK".to_string(), "1".to_string())]);
+    let output = precmd(&project, &root, &env);
+    apply(&output, &mut env);
+
+    measure("unchanged", || {
+        assert!(precmd(&project, &root, &env).is_empty());
+    });
+
+    let mut stale = env.clone();
+    stale.remove("RV_FINGERPRINT");
+    measure("recomputed", || {
+        precmd(&project, &root, &stale);
+    });
+
+    std::fs::remove_dir_all(root).unwrap();
+}
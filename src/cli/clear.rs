@@ -1,43 +1,67 @@
-use std::path::PathBuf;
+use std::time::Instant;
 
 use clap::Args;
 
-use crate::{config::Config, metadata::Metadata};
+use crate::{
+    config::{Config, Message, Verbosity},
+    metadata::Metadata,
+    Changes,
+};
 
 #[derive(Args, Debug)]
-pub struct Clear;
+pub struct Clear {
+    /// Deactivates the global profile instead
+    #[arg(long)]
+    pub global: bool,
+}
 
 impl Clear {
     pub fn clear(&self) {
+        let start = Instant::now();
         let config = Config::load();
         let mut metadata = Metadata::load();
 
+        // The variables stay exported until the next prompt unsets them.
+        if self.global {
+            let global_profile = metadata.global.take();
+            if let Some(global_profile) = global_profile {
+                let changes = Changes {
+                    removed: global_profile.variables.unwrap_or_default(),
+                    ..Default::default()
+                };
+                metadata.pending_unset.extend(changes.removed.iter().cloned());
+                if config.verbosity != Verbosity::Quiet {
+                    println!("{}", config.deactivated_message(&Message {
+                        dir: "global",
+                        profile: &global_profile.name,
+                        changes: &changes,
+                        duration: start.elapsed(),
+                    }));
+                }
+            }
+            metadata.save();
+            return
+        }
+
         let current_dir = std::env::current_dir().unwrap();
-        let rv_path = PathBuf::from(&current_dir).join("rv.toml");
-        let mut cmd = String::new();
 
-        let mut unset = String::new();
-        let mut unset_changed = false;
+        let mut changes = Changes::default();
+        let mut profile_name = String::new();
         if let Some(current_profile) = metadata
             .profiles
-            .get(&rv_path) {
+            .remove(&current_dir) {
 
-            if let Some(current_vars) = current_profile.variables.clone() {
-                unset_changed = true;
-                for var in current_vars {
-                    cmd.push_str(format!("unset {}\n", var).as_str());
-                    unset.push_str(&config.removed.paint(&var));
-                }
-            }
-            metadata.profiles.remove(&rv_path);
+            profile_name = current_profile.name;
+            changes.removed = current_profile.variables.unwrap_or_default();
+            metadata.pending_unset.extend(changes.removed.iter().cloned());
         }
-        if unset_changed {
-            println!(
-                "{}{}{}",
-                config.deactivated.paint(""),
-                config.deactivated_dir.paint(current_dir.to_str().unwrap()),
-                unset,
-            );
+        if !changes.is_empty() && config.verbosity != Verbosity::Quiet {
+            println!("{}", config.deactivated_message(&Message {
+                dir: current_dir.to_str().unwrap(),
+                profile: &profile_name,
+                changes: &changes,
+                duration: start.elapsed(),
+            }));
         }
         metadata.save();
     }
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use toml_edit::{Item, TableLike, Value};
+
+use crate::{encryption, fail};
+
+use super::encrypt::{load, locate, profile, rv_path};
+
+#[derive(Args, Debug)]
+pub struct Decrypt {
+    /// Key of the variable in rv.toml, relative to the profile (every encrypted value if omitted)
+    pub key: Option<String>,
+    #[arg(long)]
+    pub profile: Option<String>,
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+}
+
+impl Decrypt {
+    pub fn decrypt(&self) {
+        let rv_path = rv_path(self.path.as_ref());
+        let mut doc = load(&rv_path);
+
+        match self.key.as_ref() {
+            Some(key) => {
+                let profile = profile(&rv_path, self.profile.as_ref());
+                let Some(item) = locate(&mut doc, profile.as_deref(), key) else {
+                    fail(&format!("{} not found in {}", key, rv_path.display()));
+                };
+                if item.get("age").is_none() {
+                    fail(&format!("{} is not encrypted", key));
+                }
+                decrypt_item(key, item);
+            },
+            None => decrypt_table(doc.as_table_mut()),
+        }
+        std::fs::write(&rv_path, doc.to_string()).unwrap();
+    }
+}
+
+fn decrypt_table(table: &mut dyn TableLike) {
+    let keys: Vec<String> = table
+        .iter()
+        .map(|(k, _)| k.to_string())
+        .collect();
+    for key in keys {
+        let item = table.get_mut(&key).unwrap();
+        if item.get("age").is_some() {
+            decrypt_item(&key, item);
+        } else if let Some(inner) = item.as_table_like_mut() {
+            decrypt_table(inner);
+        }
+    }
+}
+
+/// Replaces `{ age = "..." }` with the plain value, keeping any other keys
+/// of the table.
+fn decrypt_item(key: &str, item: &mut Item) {
+    let armored = item
+        .get("age")
+        .and_then(Item::as_str)
+        .unwrap_or_default();
+    let plaintext = match encryption::decrypt(armored) {
+        Ok(plaintext) => plaintext,
+        Err(err) => fail(&format!("could not decrypt {}: {:#}", key, err)),
+    };
+    match item {
+        Item::Value(Value::InlineTable(old)) if old.len() == 1 => {
+            let mut new = Value::from(plaintext);
+            *new.decor_mut() = old.decor().clone();
+            *item = Item::Value(new);
+        },
+        item => {
+            let table = item.as_table_like_mut().unwrap();
+            table.remove("age");
+            table.insert("value", toml_edit::value(plaintext));
+            table.fmt();
+        },
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use toml_edit::{DocumentMut, InlineTable, Item, Value};
+
+use crate::{config::Config, encryption, fail, find_rv, metadata::Metadata};
+
+#[derive(Args, Debug)]
+pub struct Encrypt {
+    /// Key of the variable in rv.toml, relative to the profile (e.g. `db.password`)
+    pub key: String,
+    #[arg(long)]
+    pub profile: Option<String>,
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+    /// Additional age recipients (`age1...`) that can decrypt the value
+    #[arg(long)]
+    pub recipient: Vec<String>,
+}
+
+impl Encrypt {
+    pub fn encrypt(&self) {
+        let rv_path = rv_path(self.path.as_ref());
+        let mut doc = load(&rv_path);
+
+        let mut recipients = self.recipient.clone();
+        if let Some(array) = doc.get("recipients").and_then(Item::as_array) {
+            recipients.extend(array.iter().filter_map(|r| r.as_str().map(String::from)));
+        }
+
+        let profile = profile(&rv_path, self.profile.as_ref());
+        let Some(item) = locate(&mut doc, profile.as_deref(), &self.key) else {
+            fail(&format!("{} not found in {}", self.key, rv_path.display()));
+        };
+        if item.get("age").is_some() {
+            fail(&format!("{} is already encrypted", self.key));
+        }
+        let plaintext = match item.as_str().or_else(|| item.get("value").and_then(Item::as_str)) {
+            Some(plaintext) => plaintext.to_string(),
+            None => fail(&format!("{} is not a string", self.key)),
+        };
+        let armored = match encryption::encrypt(&plaintext, &recipients) {
+            Ok(armored) => armored,
+            Err(err) => fail(&format!("{:#}", err)),
+        };
+
+        match item {
+            Item::Value(Value::String(old)) => {
+                let mut table = InlineTable::new();
+                table.insert("age", armored.into());
+                let mut new = Value::InlineTable(table);
+                *new.decor_mut() = old.decor().clone();
+                *item = Item::Value(new);
+            },
+            item => {
+                let table = item.as_table_like_mut().unwrap();
+                table.remove("value");
+                table.insert("age", toml_edit::value(armored));
+                table.fmt();
+            },
+        }
+        std::fs::write(&rv_path, doc.to_string()).unwrap();
+    }
+}
+
+/// Returns the rv file of `path` or the current directory. Only TOML files
+/// are edited in place.
+pub fn rv_path(path: Option<&PathBuf>) -> PathBuf {
+    let dir = match path {
+        Some(inner) => inner.clone(),
+        None => std::env::current_dir().unwrap(),
+    };
+    let Some(rv_path) = find_rv(&dir, &Config::load()) else {
+        fail(&format!("no rv file found in {}", dir.display()))
+    };
+    if rv_path.extension().is_some_and(|extension| extension != "toml") {
+        fail(&format!("{} is not a TOML file", rv_path.display()));
+    }
+    rv_path
+}
+
+pub fn load(rv_path: &PathBuf) -> DocumentMut {
+    let rv_file = match std::fs::read_to_string(rv_path) {
+        Ok(rv_file) => rv_file,
+        Err(err) => fail(&format!("could not read {}: {}", rv_path.display(), err)),
+    };
+    match rv_file.parse() {
+        Ok(doc) => doc,
+        Err(err) => fail(&format!("invalid {}: {}", rv_path.display(), err)),
+    }
+}
+
+/// Returns the given profile, or the profile activated for `rv_path`.
+pub fn profile(rv_path: &Path, profile: Option<&String>) -> Option<String> {
+    match profile {
+        Some(inner) => Some(inner.clone()),
+        None => Metadata::load()
+            .profiles
+            .get(rv_path.parent().unwrap())
+            .map(|p| p.name.clone()),
+    }
+}
+
+/// Finds `key` in the profile table, falling back to the top level of the
+/// document.
+pub fn locate<'a>(doc: &'a mut DocumentMut, profile: Option<&str>, key: &str) -> Option<&'a mut Item> {
+    let key: Vec<&str> = key.split('.').collect();
+    let mut path: Vec<&str> = match profile {
+        Some(profile) => profile.split('.').collect(),
+        None => Vec::new(),
+    };
+    path.extend(&key);
+    let lookup = |path: &[&str]| path
+        .iter()
+        .try_fold(doc.as_item(), |item, k| item.get(k))
+        .is_some();
+    if !lookup(&path) {
+        path = key;
+        if !lookup(&path) {
+            return None
+        }
+    }
+    path
+        .iter()
+        .try_fold(doc.as_item_mut(), |item, k| item.get_mut(k))
+}
@@ -1,44 +1,37 @@
-use std::{collections::HashMap, path::PathBuf};
-
 use clap::Args;
-use toml::Value;
 
-use crate::{metadata::Metadata, rv_to_map};
+use crate::{config::Config, find_rv, load_rv, metadata::Metadata, profile_to_map};
 
 #[derive(Args, Debug)]
 pub struct Get {
     pub key: String,
+    /// Print the value of a secret variable instead of masking it
+    #[arg(long)]
+    pub reveal: bool,
 }
 
 impl Get {
     pub fn get(&self) {
+        let config = Config::load();
         let metadata = Metadata::load();
 
         let current_dir = std::env::current_dir().unwrap();
-        let rv_path = PathBuf::from(&current_dir).join("rv.toml");
-        let mut result: HashMap<String, String> = HashMap::new();
-        if rv_path.exists() {
+        if let Some(rv_path) = find_rv(&current_dir, &config) {
             if let Some(current_pwd) = metadata
                 .profiles
-                .get(&rv_path) {
+                .get(&current_dir) {
 
                 let current_profile = current_pwd.name.clone();
             
-                let rv_file = std::fs::read_to_string(rv_path.to_str().unwrap()).unwrap();
 
-                let mut rv: Value = toml::from_str(&rv_file).unwrap();
-                for (key, value) in rv.as_table().unwrap() {
-                    if let Value::String(value) = value {
-                        result.insert(key.clone(), value.clone());
-                    }
-                }
-                for value in current_profile.split('.') {
-                    rv = rv.get(value).unwrap().clone();
-                }
+                let rv = load_rv(&rv_path);
+                let result = profile_to_map(&rv, &current_dir, &current_profile, &None).unwrap();
 
-                rv_to_map(None, &mut rv, &mut result, &None);
-
-                println!("{}", result.get(&self.key).unwrap_or(&String::from("null")));
+                match result.get(&self.key) {
+                    Some(variable) if self.reveal => println!("{}", variable.value),
+                    Some(variable) => println!("{}", variable.display()),
+                    None => println!("null"),
+                }
             }
         }
     }
@@ -1,10 +1,12 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::path::PathBuf;
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use clap::{Args, ValueEnum};
-use convert_case::{Case as ConvertCase, Casing};
-use toml::Value;
+use convert_case::Case as ConvertCase;
+use indexmap::IndexMap;
+use serde::Serialize;
 
-use crate::{metadata::Metadata, rv_to_map};
+use crate::{config::Config, fail, find_rv, load_rv, metadata::Metadata, profile_to_map};
 
 #[derive(Args, Debug)]
 pub struct List {
@@ -16,6 +18,21 @@ pub struct List {
     pub profile: Option<String>,
     #[arg(long)]
     pub path: Option<PathBuf>,
+    /// Sort variables by name instead of keeping the order of rv.toml
+    #[arg(long)]
+    pub sort: bool,
+    /// Name of the Kubernetes ConfigMap or Secret (defaults to the profile name)
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Namespace of the Kubernetes ConfigMap or Secret
+    #[arg(long)]
+    pub namespace: Option<String>,
+    /// Variables whose values are masked in CI logs (github-env and github-output formats)
+    #[arg(long, value_delimiter = ',')]
+    pub mask: Vec<String>,
+    /// Include secret variables, which are left out by default
+    #[arg(long)]
+    pub reveal: bool,
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -81,94 +98,499 @@ pub enum Format {
     DockerArgs,
     /// `-var var=value`   inline Terraform CLI variables
     TfvarsArgs,
+    /// `var: value`       .yaml
+    Yaml,
+    /// `data: {var: value}` Kubernetes ConfigMap manifest
+    K8sConfigmap,
+    /// `data: {var: base64}` Kubernetes Secret manifest
+    K8sSecret,
+    /// `var="value"`      systemd EnvironmentFile
+    Systemd,
+    /// `var<<EOF`         GitHub Actions $GITHUB_ENV file
+    GithubEnv,
+    /// `var<<EOF`         GitHub Actions $GITHUB_OUTPUT file
+    GithubOutput,
+    /// `var=value`        GitLab CI dotenv report artifact
+    GitlabDotenv,
+    /// `set -gx var value` fish
+    Fish,
+    /// `load-env {var: value}` nushell
+    Nushell,
+    /// `$env:var = 'value'` PowerShell
+    Powershell,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    api_version: &'a str,
+    kind: &'a str,
+    metadata: ManifestMetadata,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    secret_type: Option<&'a str>,
+    data: IndexMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct ManifestMetadata {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
 }
 
 impl List {
     pub fn list(&self) {
+        let config = Config::load();
         let metadata = Metadata::load();
 
-        let rv_path = match self.path.as_ref() {
-            Some(inner) => inner.clone().join("rv.toml"),
-            None => {
-                let current_dir = std::env::current_dir().unwrap();
-                PathBuf::from(&current_dir).join("rv.toml")
-            },
+        let dir = match self.path.as_ref() {
+            Some(inner) => inner.clone(),
+            None => std::env::current_dir().unwrap(),
         };
-        if !rv_path.exists() {
+        let Some(rv_path) = find_rv(&dir, &config) else {
             return
-        }
-
-        let mut result: HashMap<String, String> = HashMap::new();
+        };
 
         let current_profile = match self.profile.as_ref() {
             Some(inner) => inner.clone(),
             None => metadata
                .profiles
-               .get(&rv_path)
+               .get(&dir)
                .unwrap()
                .name
                .clone(),
         };
 
-        let rv_file = std::fs::read_to_string(rv_path.to_str().unwrap()).unwrap();
+        let rv = load_rv(&rv_path);
+        let mut variables = profile_to_map(&rv, &dir, &current_profile, &self.case).unwrap();
+        if self.sort {
+            variables.sort_keys();
+        }
+
+        let hidden: Vec<&str> = variables
+            .iter()
+            .filter(|(_, v)| v.secret && !self.reveal)
+            .map(|(k, _)| k.as_str())
+            .collect();
+        if !hidden.is_empty() {
+            eprintln!("rv: left out secret variables, use --reveal to include them: {}", hidden.join(" "));
+        }
 
-        let mut rv: Value = toml::from_str(&rv_file).unwrap();
-        for (key, value) in rv.as_table().unwrap() {
-            if let Value::String(value) = value {
-                let mut key = key.clone();
-                if let Some(case) = self.case.as_ref() {
-                    key = key.to_case(case.clone().into());
+        if matches!(self.format, Format::GithubEnv | Format::GithubOutput) {
+            // Workflow commands are read from both output streams, so the
+            // masks are written to stderr to keep them out of the env file.
+            // Secrets that are left out must not appear in the log either.
+            let masked = variables
+                .iter()
+                .filter(|(_, v)| !v.secret || self.reveal)
+                .filter(|(k, v)| v.secret || self.mask.contains(k));
+            for (_, v) in masked {
+                for line in v.value.lines().filter(|l| !l.is_empty()) {
+                    eprintln!("::add-mask::{}", line);
                 }
-                result.insert(key, value.clone());
             }
         }
-        for value in current_profile.split('.') {
-            rv = rv.get(value).unwrap().clone();
-        }
 
-        rv_to_map(None, &mut rv, &mut result, &self.case);
+        let result: IndexMap<String, String> = variables
+            .into_iter()
+            .filter(|(_, v)| !v.secret || self.reveal)
+            .map(|(k, v)| (k, v.value))
+            .collect();
+
+        println!("{}", self.render(result, &current_profile).trim_end());
+    }
 
-        let list: String = match self.format {
+    /// Formats the variables of `profile` in the requested format.
+    fn render(&self, result: IndexMap<String, String>, profile: &str) -> String {
+        match self.format {
             Format::Json => serde_json::to_string_pretty(&result).unwrap(),
             Format::Toml => toml::to_string(&result).unwrap(),
+            Format::Yaml => serde_yaml::to_string(&result).unwrap(),
+            Format::K8sConfigmap => serde_yaml::to_string(&Manifest {
+                api_version: "v1",
+                kind: "ConfigMap",
+                metadata: self.manifest_metadata(profile),
+                secret_type: None,
+                data: result,
+            }).unwrap(),
+            Format::K8sSecret => serde_yaml::to_string(&Manifest {
+                api_version: "v1",
+                kind: "Secret",
+                metadata: self.manifest_metadata(profile),
+                secret_type: Some("Opaque"),
+                data: result
+                    .into_iter()
+                    .map(|(k, v)| (k, BASE64.encode(v)))
+                    .collect(),
+            }).unwrap(),
+            Format::Systemd => result
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, systemd_quote(v)))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Format::GithubEnv | Format::GithubOutput => result
+                .iter()
+                .map(|(k, v)| github_entry(k, v))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Format::Fish => result
+                .iter()
+                .map(|(k, v)| format!("set -gx {} {}", k, fish_quote(v)))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Format::Nushell => {
+                let record = result
+                    .iter()
+                    .map(|(k, v)| format!("    {}: {}", nushell_quote(k), nushell_quote(v)))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!("load-env {{\n{}\n}}", record)
+            },
+            Format::Powershell => result
+                .iter()
+                .map(|(k, v)| format!("${{env:{}}} = {}", k, powershell_quote(v)))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Format::GitlabDotenv => {
+                if let Some((k, _)) = result.iter().find(|(_, v)| v.contains(['\n', '\r'])) {
+                    fail(&format!("{} contains a newline, which GitLab dotenv reports do not support", k));
+                }
+                result
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, dotenv_quote(v)))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            },
             Format::Env => result
                 .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
+                .map(|(k, v)| format!("{}={}", k, dotenv_quote(v)))
                 .collect::<Vec<String>>()
                 .join("\n"),
             Format::Envrc => result
                 .iter()
-                .map(|(k, v)| format!("export {}={}", k, v))
+                .map(|(k, v)| format!("export {}={}", k, shell_quote(v)))
                 .collect::<Vec<String>>()
                 .join("\n"),
             Format::Args => result
                 .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
+                .map(|(k, v)| shell_quote(&format!("{}={}", k, v)))
                 .collect::<Vec<String>>()
                 .join(" "),
             Format::DockerArgs => result
                 .iter()
-                .map(|(k, v)| format!("-e {}={}", k, v))
+                .map(|(k, v)| format!("-e {}", shell_quote(&format!("{}={}", k, v))))
                 .collect::<Vec<String>>()
                 .join(" "),
             Format::TfvarsArgs => result
                 .iter()
-                .map(|(k, v)| format!("-var {}={}", k, v))
+                .map(|(k, v)| format!("-var {}", shell_quote(&format!("{}={}", k, v))))
                 .collect::<Vec<String>>()
                 .join(" "),
             Format::Tfvars => {
                 let longest = result
                     .keys()
-                    .max_by_key(|k| k.len())
-                    .unwrap()
-                    .len();
+                    .map(|k| k.len())
+                    .max()
+                    .unwrap_or(0);
                 result
                     .iter()
-                    .map(|(k, v)| format!("{:<longest$} = {:?}", k, v))
+                    .map(|(k, v)| format!("{:<longest$} = {}", k, hcl_quote(v)))
                     .collect::<Vec<String>>()
                     .join("\n")
             },
+        }
+    }
+
+    fn manifest_metadata(&self, profile: &str) -> ManifestMetadata {
+        let name = match self.name.as_ref() {
+            Some(inner) => inner.clone(),
+            None => profile
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+                .collect(),
         };
-        println!("{}", list);
+        ManifestMetadata {
+            name,
+            namespace: self.namespace.clone(),
+        }
+    }
+}
+
+fn is_bare_word(s: &str) -> bool {
+    !s.is_empty() && s
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-./:@,+%=".contains(c))
+}
+
+/// Quotes a value so that a POSIX shell reads it back as a single literal word.
+pub fn shell_quote(s: &str) -> String {
+    if is_bare_word(s) {
+        return s.to_string()
+    }
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Quotes a value following the dotenv conventions shared by Docker Compose,
+/// python-dotenv and dotenvy: single quotes are literal, double quotes
+/// understand backslash escapes. Only the escapes dotenvy reads back are
+/// used, so other characters such as `\r` are kept as is.
+fn dotenv_quote(s: &str) -> String {
+    if is_bare_word(s) {
+        return s.to_string()
+    }
+    if !s.contains(['\'', '\n', '\r']) {
+        return format!("'{}'", s)
+    }
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '$' => quoted.push_str("\\$"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a value as an HCL string literal, escaping template sequences so
+/// Terraform does not interpolate them.
+fn hcl_quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '$' | '%' if chars.peek() == Some(&'{') => {
+                quoted.push(c);
+                quoted.push(c);
+            },
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a value for a systemd `EnvironmentFile`, where double quotes
+/// understand backslash escapes and may span several lines.
+fn systemd_quote(s: &str) -> String {
+    if is_bare_word(s) {
+        return s.to_string()
+    }
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        if "\"\\`$".contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats an entry for GitHub Actions environment files, switching to the
+/// heredoc-style `name<<delimiter` syntax for multiline values.
+fn github_entry(k: &str, v: &str) -> String {
+    if !v.contains(['\n', '\r']) {
+        return format!("{}={}", k, v)
+    }
+    let mut delimiter = String::from("RV_EOF");
+    let mut n = 0;
+    while v.lines().any(|l| l == delimiter) {
+        n += 1;
+        delimiter = format!("RV_EOF_{}", n);
+    }
+    format!("{}<<{}\n{}\n{}", k, delimiter, v, delimiter)
+}
+
+/// Quotes a value for fish, where only `\\` and `'` are special inside
+/// single quotes.
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Quotes a value as a nushell double-quoted string.
+fn nushell_quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Quotes a value as a PowerShell verbatim string, where a quote is escaped
+/// by doubling it.
+fn powershell_quote(s: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in s.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(format: Format) -> List {
+        List {
+            case: None,
+            format,
+            profile: None,
+            path: None,
+            sort: false,
+            name: None,
+            namespace: Some("ci".to_string()),
+            mask: Vec::new(),
+            reveal: false,
+        }
+    }
+
+    /// Values exercising the quoting of every format. GitLab dotenv reports
+    /// cannot hold newlines.
+    fn variables(format: &Format) -> IndexMap<String, String> {
+        let mut variables = IndexMap::from([
+            ("PLAIN".to_string(), "value".to_string()),
+            ("SPACED".to_string(), "hello world".to_string()),
+            ("QUOTED".to_string(), "it's \"$HOME\" `pwd`\\".to_string()),
+            ("MULTI".to_string(), "one\ntwo\r".to_string()),
+        ]);
+        if matches!(format, Format::GitlabDotenv) {
+            variables.shift_remove("MULTI");
+        }
+        variables
+    }
+
+    fn render(format: Format) -> String {
+        list(format.clone()).render(variables(&format), "dev")
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(render(Format::Json), "{\n  \"PLAIN\": \"value\",\n  \"SPACED\": \"hello world\",\n  \"QUOTED\": \"it's \\\"$HOME\\\" `pwd`\\\\\",\n  \"MULTI\": \"one\\ntwo\\r\"\n}");
+    }
+
+    #[test]
+    fn toml() {
+        assert_eq!(render(Format::Toml), "PLAIN = \"value\"\nSPACED = \"hello world\"\nQUOTED = '''it's \"$HOME\" `pwd`\\'''\nMULTI = \"\"\"\none\ntwo\\r\"\"\"\n");
+    }
+
+    #[test]
+    fn tfvars() {
+        assert_eq!(render(Format::Tfvars), "PLAIN  = \"value\"\nSPACED = \"hello world\"\nQUOTED = \"it's \\\"$HOME\\\" `pwd`\\\\\"\nMULTI  = \"one\\ntwo\\r\"");
+    }
+
+    #[test]
+    fn env() {
+        assert_eq!(render(Format::Env), "PLAIN=value\nSPACED='hello world'\nQUOTED=\"it's \\\"\\$HOME\\\" `pwd`\\\\\"\nMULTI=\"one\\ntwo\r\"");
+    }
+
+    #[test]
+    fn envrc() {
+        assert_eq!(render(Format::Envrc), "export PLAIN=value\nexport SPACED='hello world'\nexport QUOTED='it'\\''s \"$HOME\" `pwd`\\'\nexport MULTI='one\ntwo\r'");
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(render(Format::Args), "PLAIN=value 'SPACED=hello world' 'QUOTED=it'\\''s \"$HOME\" `pwd`\\' 'MULTI=one\ntwo\r'");
+    }
+
+    #[test]
+    fn docker_args() {
+        assert_eq!(render(Format::DockerArgs), "-e PLAIN=value -e 'SPACED=hello world' -e 'QUOTED=it'\\''s \"$HOME\" `pwd`\\' -e 'MULTI=one\ntwo\r'");
+    }
+
+    #[test]
+    fn tfvars_args() {
+        assert_eq!(render(Format::TfvarsArgs), "-var PLAIN=value -var 'SPACED=hello world' -var 'QUOTED=it'\\''s \"$HOME\" `pwd`\\' -var 'MULTI=one\ntwo\r'");
+    }
+
+    #[test]
+    fn yaml() {
+        assert_eq!(render(Format::Yaml), "PLAIN: value\nSPACED: hello world\nQUOTED: it's \"$HOME\" `pwd`\\\nMULTI: \"one\\ntwo\\r\"\n");
+    }
+
+    #[test]
+    fn k8s_configmap() {
+        assert_eq!(render(Format::K8sConfigmap), "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: dev\n  namespace: ci\ndata:\n  PLAIN: value\n  SPACED: hello world\n  QUOTED: it's \"$HOME\" `pwd`\\\n  MULTI: \"one\\ntwo\\r\"\n");
+    }
+
+    #[test]
+    fn k8s_secret() {
+        assert_eq!(render(Format::K8sSecret), "apiVersion: v1\nkind: Secret\nmetadata:\n  name: dev\n  namespace: ci\ntype: Opaque\ndata:\n  PLAIN: dmFsdWU=\n  SPACED: aGVsbG8gd29ybGQ=\n  QUOTED: aXQncyAiJEhPTUUiIGBwd2RgXA==\n  MULTI: b25lCnR3bw0=\n");
+    }
+
+    #[test]
+    fn systemd() {
+        assert_eq!(render(Format::Systemd), "PLAIN=value\nSPACED=\"hello world\"\nQUOTED=\"it's \\\"\\$HOME\\\" \\`pwd\\`\\\\\"\nMULTI=\"one\ntwo\r\"");
+    }
+
+    #[test]
+    fn github_env() {
+        assert_eq!(render(Format::GithubEnv), "PLAIN=value\nSPACED=hello world\nQUOTED=it's \"$HOME\" `pwd`\\\nMULTI<<RV_EOF\none\ntwo\r\nRV_EOF");
+    }
+
+    #[test]
+    fn github_output() {
+        assert_eq!(render(Format::GithubOutput), "PLAIN=value\nSPACED=hello world\nQUOTED=it's \"$HOME\" `pwd`\\\nMULTI<<RV_EOF\none\ntwo\r\nRV_EOF");
+    }
+
+    #[test]
+    fn gitlab_dotenv() {
+        assert_eq!(render(Format::GitlabDotenv), "PLAIN=value\nSPACED='hello world'\nQUOTED=\"it's \\\"\\$HOME\\\" `pwd`\\\\\"");
+    }
+
+    #[test]
+    fn fish() {
+        assert_eq!(render(Format::Fish), "set -gx PLAIN 'value'\nset -gx SPACED 'hello world'\nset -gx QUOTED 'it\\'s \"$HOME\" `pwd`\\\\'\nset -gx MULTI 'one\ntwo\r'");
+    }
+
+    #[test]
+    fn nushell() {
+        assert_eq!(render(Format::Nushell), "load-env {\n    \"PLAIN\": \"value\"\n    \"SPACED\": \"hello world\"\n    \"QUOTED\": \"it's \\\"$HOME\\\" `pwd`\\\\\"\n    \"MULTI\": \"one\\ntwo\\r\"\n}");
+    }
+
+    #[test]
+    fn powershell() {
+        assert_eq!(render(Format::Powershell), "${env:PLAIN} = 'value'\n${env:SPACED} = 'hello world'\n${env:QUOTED} = 'it''s \"$HOME\" `pwd`\\'\n${env:MULTI} = 'one\ntwo\r'");
+    }
+
+    fn dotenvy_round_trip(format: Format) {
+        let rendered = render(format.clone());
+        let parsed: IndexMap<String, String> = dotenvy::from_read_iter(rendered.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parsed, variables(&format));
+    }
+
+    #[test]
+    fn env_round_trips_through_dotenvy() {
+        dotenvy_round_trip(Format::Env);
+    }
+
+    #[test]
+    fn gitlab_dotenv_round_trips_through_dotenvy() {
+        dotenvy_round_trip(Format::GitlabDotenv);
     }
 }
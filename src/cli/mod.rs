@@ -1,40 +1,78 @@
+use std::{io::IsTerminal, path::PathBuf};
+
 use clap::{Parser, Subcommand};
 
+use crate::config::{self, ColorChoice};
+
 use self::{
     chpwd::Chpwd,
     clear::Clear,
+    decrypt::Decrypt,
+    encrypt::Encrypt,
     get::Get,
     list::List,
+    paths::Paths,
     precmd::Precmd,
+    prompt::Prompt,
+    secret::Secret,
     set::Set,
     show::Show,
+    status::Status,
 };
 
 mod chpwd;
 mod clear;
+mod decrypt;
+mod encrypt;
 mod get;
 pub mod list;
+mod paths;
 mod precmd;
+mod prompt;
+mod secret;
 mod set;
 mod show;
+mod status;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// When to color messages
+    #[arg(long, global = true, value_enum)]
+    pub color: Option<ColorChoice>,
+    /// Config file to use instead of `RV_CONFIG` or the default location
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
 }
 
 impl Cli {
     pub fn execute(&self) {
+        // The output of the shell hooks is evaluated, and the messages they
+        // echo end up on the terminal of stderr.
+        let terminal = match &self.command {
+            Commands::Chpwd(_) | Commands::Precmd(_) => std::io::stderr().is_terminal(),
+            _ => std::io::stdout().is_terminal(),
+        };
+        config::set_color_context(self.color, terminal);
+        if let Some(path) = &self.config {
+            config::set_config_flag(path.clone());
+        }
         match &self.command {
             Commands::Set(inner) => inner.set(),
             Commands::Chpwd(inner) => inner.chpwd(),
             Commands::Precmd(inner) => inner.precmd(),
             Commands::Show(inner) => inner.show(),
+            Commands::Status(inner) => inner.status(),
             Commands::List(inner) => inner.list(),
             Commands::Get(inner) => inner.get(),
             Commands::Clear(inner) => inner.clear(),
+            Commands::Encrypt(inner) => inner.encrypt(),
+            Commands::Decrypt(inner) => inner.decrypt(),
+            Commands::Secret(inner) => inner.secret(),
+            Commands::Prompt(inner) => inner.prompt(),
+            Commands::Paths(inner) => inner.paths(),
         }
     }
 }
@@ -49,10 +87,22 @@ pub enum Commands {
     Set(Set),
     /// Shows the variables of the current profile
     Show(Show),
+    /// Shows the values of the current profile and whether they come from rv.local.toml
+    Status(Status),
     /// Outputs the variables and values of the current profile (default format is JSON)
     List(List),
     /// Outputs the value of a variable in the current profile
     Get(Get),
     /// Deactivates the current profile
     Clear(Clear),
+    /// Encrypts the value of a variable in rv.toml with age
+    Encrypt(Encrypt),
+    /// Decrypts age-encrypted values in rv.toml
+    Decrypt(Decrypt),
+    /// Manages the secrets of the local vault
+    Secret(Secret),
+    /// Prints a prompt segment with the active profile
+    Prompt(Prompt),
+    /// Prints the locations of the files rv reads and writes
+    Paths(Paths),
 }
@@ -0,0 +1,23 @@
+use clap::Args;
+
+use crate::{config::Config, global_rv_path, metadata, vault};
+
+#[derive(Args, Debug)]
+pub struct Paths;
+
+impl Paths {
+    pub fn paths(&self) {
+        let config = Config::load();
+        let paths = [
+            ("config", Config::path()),
+            ("profiles", global_rv_path()),
+            ("identity", config.identity()),
+            ("data", Config::data_dir()),
+            ("metadata", metadata::metadata_file()),
+            ("vault", vault::vault_path()),
+        ];
+        for (name, path) in paths {
+            println!("{:<8}  {}", name, path.display());
+        }
+    }
+}
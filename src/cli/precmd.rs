@@ -1,139 +1,218 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Instant};
 
 use clap::Args;
+use indexmap::IndexMap;
 use nu_ansi_term::{Color, Style};
-use toml::Value;
 
-use crate::{config::Config, metadata::Metadata, parse_rv};
+use crate::{
+    cli::list::shell_quote,
+    config::{paint, Config, Message, Verbosity},
+    find_rv,
+    global_profile_to_map,
+    is_shell_name,
+    load_rv,
+    metadata::{fingerprint, Metadata, FINGERPRINT_VAR, SESSION_PID_VAR, SESSION_VAR},
+    parse_rv,
+    profile_to_map,
+    provider,
+    take_inputs,
+    Changes,
+};
 
 #[derive(Args, Debug)]
-pub struct Precmd;
+pub struct Precmd {
+    /// PID of the calling shell (`$$`), which ties the session to it
+    #[arg(long)]
+    pub pid: Option<u32>,
+}
 
 impl Precmd {
     pub fn precmd(&self) {
+        let start = Instant::now();
+        let current_dir = std::env::current_dir().unwrap();
+        let check = std::env::var("RV_CHECK").ok();
+
+        // A session inherited from a parent shell is not ours.
+        let pid = self.pid.map(|pid| pid.to_string());
+        if pid.is_some() && std::env::var(SESSION_PID_VAR).ok() != pid {
+            std::env::remove_var(SESSION_VAR);
+        }
+
+        // Nothing to do until the directory or one of its inputs changes.
         let config = Config::load();
+        let previous_fingerprint = std::env::var(FINGERPRINT_VAR).ok();
+        if check.is_none() && previous_fingerprint.as_deref() == Some(fingerprint(&current_dir, &config).as_str()) {
+            return
+        }
+
+        provider::set_prompt();
 
         let previous_dir = std::env::var("OLDPWD").unwrap();
-        let current_dir = std::env::current_dir().unwrap();
-        let check = std::env::var("RV_CHECK").ok();
 
+        if std::env::var(SESSION_VAR).is_err() {
+            let session = Metadata::start_session();
+            std::env::set_var(SESSION_VAR, &session);
+            println!("export {}={}", SESSION_VAR, session);
+            if let Some(pid) = &pid {
+                println!("export {}={}", SESSION_PID_VAR, pid);
+            }
+        }
         let mut metadata = Metadata::load();
 
         let mut cmd = String::new();
 
-        let rv_path = PathBuf::from(&previous_dir).join("rv.toml");
-        let mut unset = String::new();
-        let mut unset_changed = false;
+        let mut deactivated = Changes::default();
+        let mut unset_vars = Vec::new();
         let mut previous_profile_name = String::new();
 
         if check.is_some() {
             // Directory changed
             if let Some(previous_profile) = metadata
                 .profiles
-                .get(&rv_path) {
+                .get(&PathBuf::from(&previous_dir)) {
 
                 previous_profile_name = previous_profile.name.clone();
                 if let Some(previous_vars) = previous_profile.variables.clone() {
-                    unset_changed = true;
-                    for var in previous_vars {
+                    for var in previous_vars.into_iter().filter(|var| is_shell_name(var)) {
                         cmd.push_str(format!("unset {}\n", var).as_str());
-                        unset.push_str(&config.removed.paint(&var));
+                        deactivated.removed.push(var.clone());
+                        unset_vars.push(var);
                     }
                 }
             }
         }
 
-        let rv_path = PathBuf::from(&current_dir).join("rv.toml");
-        let mut export = String::new();
-        let mut export_changed = false;
+        let rv_path = find_rv(&current_dir, &config);
+
+        // Without a directory change, the profiles are reloaded because one of
+        // their files changed, so keys exported at a previous prompt may be gone.
+        let mut previous_vars = Vec::new();
+        if check.is_none() {
+            for profile in metadata.global.iter().chain(metadata.profiles.get(&current_dir)) {
+                previous_vars.extend(profile.variables.clone().unwrap_or_default());
+            }
+            if rv_path.is_none() {
+                if let Some(current_profile) = metadata.profiles.get_mut(&current_dir) {
+                    current_profile.variables = None;
+                }
+            }
+        }
+
+        let mut activated = Changes::default();
         let mut current_profile_name = String::new();
-        if rv_path.exists() {
+        let mut current_map = None;
+        if let Some(rv_path) = &rv_path {
             if let Some(current_profile) = metadata
                 .profiles
-                .get_mut(&rv_path) {
+                .get(&current_dir) {
 
                 current_profile_name = current_profile.name.clone();
             
-                let rv_file = std::fs::read_to_string(rv_path.to_str().unwrap()).unwrap();
-                current_profile.variables = Some(Vec::new());
-
-                let mut rv: Value = toml::from_str(&rv_file).unwrap();
-                for (key, value) in rv.as_table().unwrap() {
-                    if let Value::String(value) = value {
-                        current_profile.variables.as_mut().unwrap().push(key.clone());
-                        if let Ok(val) = std::env::var(key) {
-                            if val != *value {
-                                export_changed = true;
-                                cmd.push_str(format!("export {}={}\n", key, value).as_str());
-                                export.push_str(&config.changed.paint(key));
-                            }
-                        } else {
-                            export_changed = true;
-                            cmd.push_str(format!("export {}={}\n", key, value).as_str());
-                            export.push_str(&config.added.paint(key));
+                let rv = load_rv(rv_path);
+                current_map = match profile_to_map(&rv, &current_dir, &current_profile_name, &None) {
+                    Some(map) => Some(map),
+                    // A profile that is gone exports nothing, so the variables
+                    // it exported before are unset.
+                    None => {
+                        if config.verbosity != Verbosity::Quiet {
+                            let profile_str = paint(Style::new().bold().fg(Color::Green), &current_profile_name);
+                            println!("echo {}", shell_quote(&format!("profile {} not found", profile_str)));
                         }
+                        Some(IndexMap::new())
+                    },
+                };
+            }
+        }
+
+        // The global profile yields to the directory profile for shared keys.
+        let mut global = Changes::default();
+        let mut global_profile_name = String::new();
+        if let Some(global_profile) = metadata.global.as_mut() {
+            global_profile_name = global_profile.name.clone();
+            global_profile.variables = Some(Vec::new());
+            match global_profile_to_map(&global_profile_name, &None) {
+                Some(mut map) => {
+                    if let Some(current_map) = &current_map {
+                        map.retain(|k, _| !current_map.contains_key(k));
                     }
-                }
-                for value in current_profile_name.split('.') {
-                    rv = match rv.get(value) {
-                        Some(value) => value.clone(),
-                        None => {
-                            let profile_str = Style::new()
-                                .bold()
-                                .fg(Color::Green)
-                                .paint(current_profile_name.clone());
-                            println!("echo 'profile {} not found'", profile_str);
-                            return
-                        }
-                    };
-                }
+                    parse_rv(&map, &unset_vars, global_profile, &mut global, &mut cmd, &config);
+                },
+                None if config.verbosity != Verbosity::Quiet => {
+                    let profile_str = paint(Style::new().bold().fg(Color::Green), &global_profile_name);
+                    println!("echo {}", shell_quote(&format!("global profile {} not found", profile_str)));
+                },
+                None => {},
+            }
+        }
+
+        if let Some(map) = current_map {
+            let current_profile = metadata.profiles.get_mut(&current_dir).unwrap();
+            current_profile.variables = Some(Vec::new());
+            parse_rv(&map, &unset_vars, current_profile, &mut activated, &mut cmd, &config);
+        }
 
-                parse_rv(None, &mut rv, current_profile, &mut export_changed, &mut cmd, &mut export, &config);
+        let exported = |var: &String| metadata.global
+            .iter()
+            .chain(metadata.profiles.get(&current_dir))
+            .filter_map(|profile| profile.variables.as_ref())
+            .any(|variables| variables.contains(var));
+        for var in previous_vars.into_iter().filter(|var| is_shell_name(var)) {
+            if !exported(&var) {
+                cmd.push_str(format!("unset {}\n", var).as_str());
+                activated.removed.push(var);
             }
+        }
+        // `rv clear` already reported the variables of the profiles it cleared.
+        let pending_unset = std::mem::take(&mut metadata.pending_unset);
+        for var in pending_unset.iter().filter(|var| is_shell_name(var)) {
+            if !exported(var) && !activated.removed.contains(var) {
+                cmd.push_str(format!("unset {}\n", var).as_str());
+            }
+        }
+        let inputs = take_inputs();
+        if rv_path.is_some() || metadata.global.is_some() || !activated.removed.is_empty() || !pending_unset.is_empty() || metadata.inputs != inputs {
+            metadata.inputs = inputs;
             metadata.save();
         }
 
         let home_dir = dirs::home_dir().unwrap();
         let home_dir = home_dir.to_str().unwrap();
 
-        let mut previous_dir = previous_dir.replace(home_dir, "~");
-        previous_dir.push(':');
-        previous_dir.push_str(previous_profile_name.as_str());
-
-        let mut current_dir = current_dir.to_str().unwrap().replace(home_dir, "~");
-        current_dir.push(':');
-        current_dir.push_str(current_profile_name.as_str());
-
-        let mut unset_len = previous_dir.len();
-        let mut export_len = current_dir.len();
-        if unset_len > export_len {
-            export_len = unset_len - export_len;
-            unset_len = 0;
-        } else {
-            unset_len = export_len - unset_len;
-            export_len = 0;
-        }
-
-        if unset_changed {
-            println!(
-                "echo '{}{}{:>unset_len$}{}'",
-                config.deactivated.paint(""),
-                config.deactivated_dir.paint(&previous_dir),
-                "",
-                unset,
-            );
+        let previous_dir = previous_dir.replace(home_dir, "~");
+        let current_dir = current_dir.to_str().unwrap().replace(home_dir, "~");
+        let duration = start.elapsed();
+
+        if config.verbosity != Verbosity::Quiet {
+            if !deactivated.is_empty() {
+                println!("echo {}", shell_quote(&config.deactivated_message(&Message {
+                    dir: &previous_dir,
+                    profile: &previous_profile_name,
+                    changes: &deactivated,
+                    duration,
+                })));
+            }
+            if !global.is_empty() {
+                println!("echo {}", shell_quote(&config.activated_message(&Message {
+                    dir: "global",
+                    profile: &global_profile_name,
+                    changes: &global,
+                    duration,
+                })));
+            }
+            if !activated.is_empty() {
+                println!("echo {}", shell_quote(&config.activated_message(&Message {
+                    dir: &current_dir,
+                    profile: &current_profile_name,
+                    changes: &activated,
+                    duration,
+                })));
+            }
         }
 
-        if export_changed {
-            println!(
-                "echo '{}{}{:>export_len$}{}'",
-                config.activated.paint(""),
-                config.activated_dir.paint(&current_dir),
-                "",
-                export,
-            );
+        let fingerprint = fingerprint(&std::env::current_dir().unwrap(), &config);
+        if previous_fingerprint.as_ref() != Some(&fingerprint) {
+            println!("export {}={}", FINGERPRINT_VAR, fingerprint);
         }
-
         println!("unset RV_CHECK");
         println!("{}", cmd);
     }
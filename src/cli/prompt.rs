@@ -0,0 +1,47 @@
+use clap::Args;
+
+use crate::{
+    config::Config,
+    find_rv,
+    metadata::{fingerprint, Metadata, FINGERPRINT_VAR},
+};
+
+#[derive(Args, Debug)]
+pub struct Prompt;
+
+impl Prompt {
+    pub fn prompt(&self) {
+        let config = Config::load();
+        let metadata = Metadata::load();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let current_profile = metadata.profiles
+            .get(&current_dir)
+            .filter(|_| find_rv(&current_dir, &config).is_some());
+        if current_profile.is_none() && metadata.global.is_none() {
+            return
+        }
+
+        // The profiles are stale if their files changed since the last
+        // activation, or if exported variables were unset by hand.
+        let exported: Vec<&String> = current_profile
+            .into_iter()
+            .chain(metadata.global.as_ref())
+            .filter_map(|profile| profile.variables.as_ref())
+            .flatten()
+            .collect();
+        let stale = std::env::var(FINGERPRINT_VAR).ok() != Some(fingerprint(&current_dir, &config))
+            || exported.iter().any(|var| std::env::var_os(var).is_none());
+
+        let count = exported.len();
+        println!(
+            "{}",
+            config.prompt_message(
+                current_profile.map(|profile| profile.name.as_str()).unwrap_or(""),
+                metadata.global.as_ref().map(|profile| profile.name.as_str()).unwrap_or(""),
+                count,
+                stale,
+            ),
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Subcommand};
+
+use crate::{fail, vault};
+
+#[derive(Args, Debug)]
+pub struct Secret {
+    #[command(subcommand)]
+    pub command: SecretCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SecretCommands {
+    /// Stores a secret in the vault (the value is prompted for if omitted)
+    Set {
+        name: String,
+        value: Option<String>,
+    },
+    /// Outputs the value of a secret
+    Get {
+        name: String,
+    },
+    /// Removes a secret from the vault
+    Rm {
+        name: String,
+    },
+    /// Outputs the names of the secrets in the vault
+    List,
+}
+
+impl Secret {
+    pub fn secret(&self) {
+        if let Err(err) = self.run() {
+            fail(&format!("{:#}", err));
+        }
+    }
+
+    fn run(&self) -> Result<()> {
+        match &self.command {
+            SecretCommands::Set { name, value } => {
+                let value = match value {
+                    Some(inner) => inner.clone(),
+                    None => vault::prompt(&format!("value of {}: ", name))?,
+                };
+                let passphrase = vault::prompt_passphrase()?;
+                let mut secrets = vault::open(&passphrase)?;
+                secrets.insert(name.clone(), value);
+                vault::save(&secrets, &passphrase)?;
+            },
+            SecretCommands::Get { name } => {
+                println!("{}", vault::resolve(name)?);
+            },
+            SecretCommands::Rm { name } => {
+                let passphrase = vault::prompt_passphrase()?;
+                let mut secrets = vault::open(&passphrase)?;
+                if secrets.shift_remove(name).is_none() {
+                    return Err(anyhow!("no secret named {} in the vault", name))
+                }
+                vault::save(&secrets, &passphrase)?;
+            },
+            SecretCommands::List => {
+                for name in vault::unlock()?.keys() {
+                    println!("{}", name);
+                }
+            },
+        }
+        Ok(())
+    }
+}
@@ -5,12 +5,33 @@ use crate::{metadata::Metadata, Profile};
 #[derive(Args, Debug)]
 pub struct Set {
     pub profile: String,
+    /// Activates a profile of profiles.toml in every directory
+    #[arg(long)]
+    pub global: bool,
+    /// Persists the profile for every shell session instead of only this one
+    #[arg(long)]
+    pub default: bool,
 }
 
 impl Set {
     pub fn set(&self) {
         let mut metadata = Metadata::load();
-        let current_dir = std::env::current_dir().unwrap().join("rv.toml");
+        if self.global {
+            let variables = metadata.global.take().and_then(|profile| profile.variables);
+            metadata.global = Some(Profile {
+                name: self.profile.to_string(),
+                variables,
+            });
+            if self.default {
+                metadata.set_default(None, &self.profile);
+            }
+            metadata.save();
+            return
+        }
+        let current_dir = std::env::current_dir().unwrap();
+        if self.default {
+            metadata.set_default(Some(&current_dir), &self.profile);
+        }
         metadata
             .profiles
             .entry(current_dir)
@@ -1,9 +1,7 @@
-use std::path::PathBuf;
-
 use clap::Args;
 use nu_ansi_term::{Color, Style};
 
-use crate::{config::Config, metadata::Metadata};
+use crate::{config::{paint, Config}, metadata::Metadata};
 
 #[derive(Args, Debug)]
 pub struct Show;
@@ -13,18 +11,28 @@ impl Show {
         let config = Config::load();
         let metadata = Metadata::load();
 
+        if let Some(global_profile) = &metadata.global {
+            if let Some(variables) = &global_profile.variables {
+                println!(
+                    "{}{} {}",
+                    config.activated.paint(""),
+                    config.activated_dir.paint(&format!("global:{}", global_profile.name)),
+                    paint(Style::new().bold().fg(Color::Green), &variables.join(" ")),
+                );
+            }
+        }
+
         let current_dir = std::env::current_dir().unwrap();
-        let rv_path = PathBuf::from(&current_dir).join("rv.toml");
         if let Some(current_profile) = metadata
             .profiles
-            .get(&rv_path) {
+            .get(&current_dir) {
             if let Some(variables) = &current_profile.variables {
                 let list: String = variables.join(" ");
                 println!(
                     "{}{} {}",
                     config.activated.paint(""),
                     config.activated_dir.paint(&current_profile.name),
-                    Style::new().bold().fg(Color::Green).paint(&list),
+                    paint(Style::new().bold().fg(Color::Green), &list),
                 );
             }
         }
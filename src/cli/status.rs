@@ -0,0 +1,68 @@
+use clap::Args;
+
+use crate::{
+    config::Config,
+    find_rv,
+    load_rv,
+    local_rv_path,
+    metadata::Metadata,
+    profile_to_map,
+    read_rv,
+};
+
+#[derive(Args, Debug)]
+pub struct Status;
+
+impl Status {
+    pub fn status(&self) {
+        let config = Config::load();
+        let metadata = Metadata::load();
+
+        let current_dir = std::env::current_dir().unwrap();
+        let Some(rv_path) = find_rv(&current_dir, &config) else {
+            return
+        };
+        let Some(current_profile) = metadata.profiles.get(&current_dir) else {
+            return
+        };
+
+        let rv = load_rv(&rv_path);
+        let Some(variables) = profile_to_map(&rv, &current_dir, &current_profile.name, &None) else {
+            println!("profile {} not found", current_profile.name);
+            return
+        };
+
+        // Values that differ from the rv file alone came from the override file.
+        let local_path = local_rv_path(&rv_path);
+        let local_name = local_path.file_name().unwrap().to_string_lossy();
+        let shared = if local_path.exists() {
+            let rv = read_rv(&rv_path);
+            profile_to_map(&rv, &current_dir, &current_profile.name, &None).unwrap_or_default()
+        } else {
+            variables.clone()
+        };
+
+        let home_dir = dirs::home_dir().unwrap();
+        let mut dir = current_dir.to_str().unwrap().replace(home_dir.to_str().unwrap(), "~");
+        dir.push(':');
+        dir.push_str(&current_profile.name);
+        println!("{}{}", config.activated.paint(""), config.activated_dir.paint(&dir));
+
+        let longest = variables
+            .keys()
+            .map(|k| k.len())
+            .max()
+            .unwrap_or(0);
+        for (key, variable) in &variables {
+            let overridden = shared
+                .get(key)
+                .map(|v| v.value != variable.value)
+                .unwrap_or(true);
+            if overridden {
+                println!("{}{:<longest$} = {}  ({})", config.changed.paint(""), key, variable.display(), local_name);
+            } else {
+                println!("{}{:<longest$} = {}", config.added.paint(""), key, variable.display());
+            }
+        }
+    }
+}
@@ -1,6 +1,29 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use indexmap::IndexMap;
 use serde::Deserialize;
 use nu_ansi_term::{Color, Style};
 
+use crate::{fail, provider::ProviderConfig, Changes};
+
+/// Whether formats emit ANSI escape codes, resolved by `Config::load`.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// The `--color` flag and whether the output of the command reaches a
+/// terminal, set before the configuration is loaded.
+static COLOR_CONTEXT: OnceLock<(Option<ColorChoice>, bool)> = OnceLock::new();
+
+/// The `--config` flag, set before the configuration is loaded.
+static CONFIG_FLAG: OnceLock<PathBuf> = OnceLock::new();
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(default = "default_activated")]
@@ -17,14 +40,120 @@ pub struct Config {
     pub removed: Format,
     #[serde(default = "default_changed")]
     pub changed: Format,
+    #[serde(default = "default_prompt")]
+    pub prompt: Format,
+    #[serde(default = "default_stale")]
+    pub stale: Format,
+    /// File names searched for profiles, in order of precedence
+    #[serde(default = "default_files")]
+    pub files: Vec<String>,
+    /// Layout of the messages printed when profiles are activated
+    #[serde(default)]
+    pub templates: Templates,
+    /// Detail of the messages printed when profiles are activated
+    #[serde(default)]
+    pub verbosity: Verbosity,
+    /// When to color messages
+    #[serde(default)]
+    pub color: ColorChoice,
+    /// age identity file used to decrypt encrypted values
+    #[serde(default)]
+    pub identity: Option<PathBuf>,
+    /// Seconds a shell session keeps the secret vault unlocked
+    #[serde(default = "default_vault_timeout")]
+    pub vault_timeout: u64,
+    /// External secret providers, by name
+    #[serde(default)]
+    pub providers: IndexMap<String, ProviderConfig>,
 }
 
 impl Config {
+    /// Returns the config file: the `--config` flag, `RV_CONFIG`, or
+    /// `$XDG_CONFIG_HOME/rv/config.toml`.
+    pub fn path() -> PathBuf {
+        if let Some(path) = CONFIG_FLAG.get() {
+            return path.clone()
+        }
+        match std::env::var_os("RV_CONFIG").filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => dirs::config_dir().unwrap().join("rv").join("config.toml"),
+        }
+    }
+
+    /// Returns the directory of the config file, which also holds the global
+    /// profiles and the age identity.
+    pub fn dir() -> PathBuf {
+        Config::path().parent().unwrap().to_path_buf()
+    }
+
+    /// Returns the directory of the activation state and the vault:
+    /// `RV_DATA_DIR`, or `$XDG_DATA_HOME/rv`.
+    pub fn data_dir() -> PathBuf {
+        match std::env::var_os("RV_DATA_DIR").filter(|path| !path.is_empty()) {
+            Some(path) => PathBuf::from(path),
+            None => dirs::data_dir().unwrap().join("rv"),
+        }
+    }
+
     pub fn load() -> Config {
-        let config_path = dirs::config_dir().unwrap().join("rv").join("config.toml");
-        match std::fs::read_to_string(config_path) {
-            Ok(config) => toml::from_str(config.as_str()).unwrap(),
+        let config: Config = match std::fs::read_to_string(Config::path()) {
+            Ok(config) => match toml::from_str(config.as_str()) {
+                Ok(config) => config,
+                Err(e) => fail(&format!("invalid {}: {}", Config::path().display(), e)),
+            },
             Err(_) => Config::default(),
+        };
+        COLOR.store(color_enabled(config.color), Ordering::Relaxed);
+        config
+    }
+
+    /// Renders the message of a profile activated in a directory.
+    pub fn activated_message(&self, message: &Message) -> String {
+        self.message(&self.templates.activated, &self.activated, &self.activated_dir, message)
+    }
+
+    /// Renders the message of a profile deactivated in a directory.
+    pub fn deactivated_message(&self, message: &Message) -> String {
+        self.message(&self.templates.deactivated, &self.deactivated, &self.deactivated_dir, message)
+    }
+
+    fn message(&self, template: &str, symbol: &Format, dir: &Format, message: &Message) -> String {
+        let paint = |format: &Format, keys: &[String]| -> String {
+            keys.iter().map(|key| format.paint(key)).collect()
+        };
+        let changes = message.changes;
+        render(template, |name| Some(match name {
+            "symbol" => symbol.paint(""),
+            "dir" => dir.paint(message.dir),
+            "profile" => dir.paint(message.profile),
+            "added" => paint(&self.added, &changes.added),
+            "changed" => paint(&self.changed, &changes.changed),
+            "removed" => paint(&self.removed, &changes.removed),
+            "added_count" => changes.added.len().to_string(),
+            "changed_count" => changes.changed.len().to_string(),
+            "removed_count" => changes.removed.len().to_string(),
+            "duration" => format!("{}ms", message.duration.as_millis()),
+            _ => return None,
+        }))
+    }
+
+    /// Renders the prompt segment of `rv prompt`.
+    pub fn prompt_message(&self, profile: &str, global: &str, count: usize, stale: bool) -> String {
+        render(&self.templates.prompt, |name| Some(match name {
+            "symbol" => self.prompt.paint(""),
+            "profile" => self.prompt.style(profile),
+            "global" => self.prompt.style(global),
+            "count" => count.to_string(),
+            "stale" if stale => self.stale.paint(""),
+            "stale" => String::new(),
+            _ => return None,
+        }))
+    }
+
+    pub fn identity(&self) -> PathBuf {
+        match &self.identity {
+            Some(identity) => identity.clone(),
+            None => Config::dir().join("identity.txt"),
         }
     }
 }
@@ -39,10 +168,151 @@ impl Default for Config {
             added: default_added(),
             removed: default_removed(),
             changed: default_changed(),
+            prompt: default_prompt(),
+            stale: default_stale(),
+            files: default_files(),
+            templates: Templates::default(),
+            verbosity: Verbosity::default(),
+            color: ColorChoice::default(),
+            identity: None,
+            vault_timeout: default_vault_timeout(),
+            providers: IndexMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Color output that reaches a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Records the `--config` flag.
+pub fn set_config_flag(path: PathBuf) {
+    CONFIG_FLAG.set(path).ok();
+}
+
+/// Records the `--color` flag and whether the output reaches a terminal.
+pub fn set_color_context(flag: Option<ColorChoice>, terminal: bool) {
+    COLOR_CONTEXT.set((flag, terminal)).ok();
+}
+
+/// Resolves whether to color output. The `--color` flag takes precedence over
+/// `NO_COLOR` and `CLICOLOR_FORCE`, which take precedence over config.toml.
+fn color_enabled(config: ColorChoice) -> bool {
+    let (flag, terminal) = COLOR_CONTEXT.get().copied().unwrap_or((None, false));
+    let env = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let choice = flag
+        .or_else(|| env("NO_COLOR").map(|_| ColorChoice::Never))
+        .or_else(|| env("CLICOLOR_FORCE").filter(|value| value != "0").map(|_| ColorChoice::Always))
+        .unwrap_or(config);
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal,
+    }
+}
+
+/// Paints a string with a style, unless color is disabled.
+pub fn paint(style: Style, s: &str) -> String {
+    if COLOR.load(Ordering::Relaxed) {
+        style.paint(s).to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Placeholders of an activation message.
+pub struct Message<'a> {
+    pub dir: &'a str,
+    pub profile: &'a str,
+    pub changes: &'a Changes,
+    pub duration: Duration,
+}
+
+/// Replaces the `{name}` placeholders of a template, leaving unknown ones as is.
+fn render(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let placeholder = rest
+            .find('}')
+            .and_then(|end| value(&rest[1..end]).map(|value| (end, value)));
+        match placeholder {
+            Some((end, value)) => {
+                rendered.push_str(&value);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Templates {
+    #[serde(default = "default_activated_template")]
+    pub activated: String,
+    #[serde(default = "default_deactivated_template")]
+    pub deactivated: String,
+    #[serde(default = "default_prompt_template")]
+    pub prompt: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            activated: default_activated_template(),
+            deactivated: default_deactivated_template(),
+            prompt: default_prompt_template(),
         }
     }
 }
 
+fn default_activated_template() -> String {
+    "{symbol}{dir}:{profile}{added}{changed}{removed}".to_string()
+}
+
+fn default_deactivated_template() -> String {
+    "{symbol}{dir}:{profile}{removed}".to_string()
+}
+
+fn default_prompt_template() -> String {
+    "{symbol}{profile}{stale}".to_string()
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// No activation messages
+    Quiet,
+    /// Names of the added, changed and removed variables
+    #[default]
+    Normal,
+    /// Old and new values of changed variables
+    Verbose,
+}
+
+fn default_files() -> Vec<String> {
+    ["rv.toml", ".rv.toml", "rv.yaml", "rv.yml", "rv.json"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_vault_timeout() -> u64 {
+    3600
+}
+
 fn default_activated() -> Format {
     Format {
         symbol: Some("rv ↑ ".to_string()),
@@ -92,6 +362,20 @@ fn default_changed() -> Format {
     }
 }
 
+fn default_prompt() -> Format {
+    Format {
+        symbol: Some("rv ".to_string()),
+        style: Some(Style::new().bold().fg(Color::Green)),
+    }
+}
+
+fn default_stale() -> Format {
+    Format {
+        symbol: Some("*".to_string()),
+        style: Some(Style::new().bold().fg(Color::Yellow)),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Format {
     pub symbol: Option<String>,
@@ -101,11 +385,19 @@ pub struct Format {
 
 impl Format {
     pub fn paint(&self, s: &str) -> String {
-        match &self.style {
+        match self.style.filter(|_| COLOR.load(Ordering::Relaxed)) {
             Some(style) => format!("{}{}", style.paint(self.symbol.as_deref().unwrap_or("")), style.paint(s)),
             None => format!("{}{}", self.symbol.as_deref().unwrap_or(""), s),
         }
     }
+
+    /// Paints a string with the style alone, without the symbol.
+    pub fn style(&self, s: &str) -> String {
+        match self.style {
+            Some(style) => paint(style, s),
+            None => s.to_string(),
+        }
+    }
 }
 
 fn deserialize_style<'de, D>(deserializer: D) -> Result<Option<Style>, D::Error>
@@ -114,60 +406,149 @@ where
 {
     let s: Option<String> = Option::deserialize(deserializer)?;
     match s {
-        Some(s) => {
-            let mut style = Style::new();
-            let mut s = s.split_whitespace();
-            let color = match s.next() {
-                Some("black") => Color::Black,
-                Some("darkgray") => Color::DarkGray,
-                Some("red") => Color::Red,
-                Some("lightred") => Color::LightRed,
-                Some("green") => Color::Green,
-                Some("lightgreen") => Color::LightGreen,
-                Some("yellow") => Color::Yellow,
-                Some("lightyellow") => Color::LightYellow,
-                Some("blue") => Color::Blue,
-                Some("lightblue") => Color::LightBlue,
-                Some("purple") => Color::Purple,
-                Some("lightpurple") => Color::LightPurple,
-                Some("magenta") => Color::Magenta,
-                Some("lightmagenta") => Color::LightMagenta,
-                Some("cyan") => Color::Cyan,
-                Some("lightcyan") => Color::LightCyan,
-                Some("white") => Color::White,
-                Some("lightgray") => Color::LightGray,
-                Some("default") => Color::Default,
-                Some(bits) => {
-                    match bits.parse() {
-                        Ok(fixed) => Color::Fixed(fixed),
-                        Err(_) => {
-                            let mut rgb = bits.split(',');
-                            let r = rgb.next().unwrap().parse().unwrap();
-                            let g = rgb.next().unwrap().parse().unwrap();
-                            let b = rgb.next().unwrap().parse().unwrap();
-                            Color::Rgb(r, g, b)
-                        },
-                    }
-                }
-                _ => return Ok(None),
-            };
-            style = style.fg(color);
-            for elem in s {
-                style = match elem {
-                    "bold" => style.bold(),
-                    "dimmed" => style.dimmed(),
-                    "italic" => style.italic(),
-                    "underline" => style.underline(),
-                    "blink" => style.blink(),
-                    "reverse" => style.reverse(),
-                    "hidden" => style.hidden(),
-                    "strikethrough" => style.strikethrough(),
-                    _ => style,
-                }
+        Some(s) if !s.trim().is_empty() => parse_style(&s)
+            .map(Some)
+            .map_err(|e| serde::de::Error::custom(format!("invalid style {:?}: {}", s, e))),
+        _ => Ok(None),
+    }
+}
+
+/// Parses a style such as `bold fg:#ff8800 bg:236`, following the starship
+/// syntax. A bare color sets the foreground, and words may come in any order.
+fn parse_style(s: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    for word in s.split_whitespace() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.bold(),
+            "dimmed" => style.dimmed(),
+            "italic" => style.italic(),
+            "underline" => style.underline(),
+            "blink" => style.blink(),
+            "reverse" | "inverted" => style.reverse(),
+            "hidden" => style.hidden(),
+            "strikethrough" => style.strikethrough(),
+            "none" => style,
+            word => match word.split_once(':') {
+                Some(("fg", color)) => style.fg(parse_color(color)?),
+                Some(("bg", color)) => style.on(parse_color(color)?),
+                Some((prefix, _)) => return Err(format!("unknown prefix {:?}, expected fg or bg", prefix)),
+                None => style.fg(parse_color(word)?),
+            },
+        }
+    }
+    Ok(style)
+}
+
+/// Parses a color name, a 0–255 palette index, `#rrggbb` or `r,g,b`.
+fn parse_color(s: &str) -> Result<Color, String> {
+    let color = match s {
+        "black" => Color::Black,
+        "darkgray" | "bright-black" => Color::DarkGray,
+        "red" => Color::Red,
+        "lightred" | "bright-red" => Color::LightRed,
+        "green" => Color::Green,
+        "lightgreen" | "bright-green" => Color::LightGreen,
+        "yellow" => Color::Yellow,
+        "lightyellow" | "bright-yellow" => Color::LightYellow,
+        "blue" => Color::Blue,
+        "lightblue" | "bright-blue" => Color::LightBlue,
+        "purple" => Color::Purple,
+        "lightpurple" | "bright-purple" => Color::LightPurple,
+        "magenta" => Color::Magenta,
+        "lightmagenta" | "bright-magenta" => Color::LightMagenta,
+        "cyan" => Color::Cyan,
+        "lightcyan" | "bright-cyan" => Color::LightCyan,
+        "white" => Color::White,
+        "lightgray" | "bright-white" => Color::LightGray,
+        "default" | "none" => Color::Default,
+        hex if hex.starts_with('#') => {
+            let hex = &hex[1..];
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("invalid hex color {:?}, expected #rrggbb", s))
             }
-            Ok(Some(style))
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+            Color::Rgb(channel(0), channel(2), channel(4))
         },
-        None => Ok(None),
-    }
+        rgb if rgb.contains(',') => {
+            let channels: Vec<u8> = rgb
+                .split(',')
+                .map(|c| c.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("invalid rgb color {:?}, expected r,g,b from 0 to 255", s))?;
+            match channels[..] {
+                [r, g, b] => Color::Rgb(r, g, b),
+                _ => return Err(format!("invalid rgb color {:?}, expected r,g,b from 0 to 255", s)),
+            }
+        },
+        fixed => match fixed.parse() {
+            Ok(fixed) => Color::Fixed(fixed),
+            Err(_) => return Err(format!("unknown color {:?}", s)),
+        },
+    };
+    Ok(color)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse_style("#ff8800"), Ok(Style::new().fg(Color::Rgb(255, 136, 0))));
+        assert_eq!(parse_style("#FF8800"), Ok(Style::new().fg(Color::Rgb(255, 136, 0))));
+    }
+
+    #[test]
+    fn parses_foreground_and_background() {
+        assert_eq!(
+            parse_style("bold fg:red bg:236"),
+            Ok(Style::new().bold().fg(Color::Red).on(Color::Fixed(236))),
+        );
+        assert_eq!(
+            parse_style("bg:#000000 underline 10,20,30"),
+            Ok(Style::new().on(Color::Rgb(0, 0, 0)).underline().fg(Color::Rgb(10, 20, 30))),
+        );
+        assert_eq!(parse_style("none"), Ok(Style::new()));
+    }
+
+    #[test]
+    fn rejects_rgb_out_of_range() {
+        assert_eq!(
+            parse_style("fg:255,256,0"),
+            Err("invalid rgb color \"255,256,0\", expected r,g,b from 0 to 255".to_string()),
+        );
+        assert_eq!(
+            parse_style("1,2"),
+            Err("invalid rgb color \"1,2\", expected r,g,b from 0 to 255".to_string()),
+        );
+    }
+
+    #[test]
+    fn rejects_bad_hex_colors() {
+        assert_eq!(
+            parse_style("bold #ff880"),
+            Err("invalid hex color \"#ff880\", expected #rrggbb".to_string()),
+        );
+        assert_eq!(
+            parse_style("bg:#gg0000"),
+            Err("invalid hex color \"#gg0000\", expected #rrggbb".to_string()),
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_words() {
+        assert_eq!(parse_style("blod"), Err("unknown color \"blod\"".to_string()));
+        assert_eq!(parse_style("256"), Err("unknown color \"256\"".to_string()));
+        assert_eq!(
+            parse_style("on:red"),
+            Err("unknown prefix \"on\", expected fg or bg".to_string()),
+        );
+    }
+
+    #[test]
+    fn reports_the_style_in_config_errors() {
+        let err = toml::from_str::<Format>("symbol = \"*\"\nstyle = \"bold blod\"").unwrap_err();
+        assert!(err.message().contains("invalid style \"bold blod\": unknown color \"blod\""));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    io::{Read, Write},
+    iter,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use age::{
+    armor::{ArmoredReader, ArmoredWriter, Format},
+    scrypt,
+    secrecy::SecretString,
+    Decryptor, Encryptor, Identity, IdentityFile, NoCallbacks, Recipient,
+};
+use anyhow::{anyhow, Context, Result};
+
+use crate::config::Config;
+
+static IDENTITY: OnceLock<PathBuf> = OnceLock::new();
+
+/// Returns the path of the age identity file configured in `config.toml`.
+pub fn identity_path() -> &'static Path {
+    IDENTITY.get_or_init(|| Config::load().identity())
+}
+
+fn identity_file() -> Result<IdentityFile<NoCallbacks>> {
+    let path = identity_path();
+    IdentityFile::from_file(path.to_string_lossy().to_string())
+        .with_context(|| format!(
+            "could not read the age identity {} (create one with `age-keygen -o {}`)",
+            path.display(),
+            path.display(),
+        ))
+}
+
+/// Decrypts an ASCII-armored age file with the local identity.
+pub fn decrypt(armored: &str) -> Result<String> {
+    let identities = identity_file()?.into_identities()?;
+    decrypt_with(armored, identities.iter().map(|i| i.as_ref() as &dyn Identity))
+}
+
+/// Decrypts an ASCII-armored age file with a passphrase.
+pub fn decrypt_with_passphrase(armored: &str, passphrase: &str) -> Result<String> {
+    let identity = scrypt::Identity::new(SecretString::from(passphrase));
+    decrypt_with(armored, iter::once(&identity as &dyn Identity))
+        .context("wrong passphrase")
+}
+
+pub fn decrypt_with<'a>(armored: &str, identities: impl Iterator<Item = &'a dyn Identity>) -> Result<String> {
+    let decryptor = Decryptor::new_buffered(ArmoredReader::new(armored.as_bytes()))?;
+    let mut reader = decryptor.decrypt(identities)?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+/// Encrypts `plaintext` to the local identity and the given `age1...`
+/// recipients, returning an ASCII-armored age file.
+pub fn encrypt(plaintext: &str, recipients: &[String]) -> Result<String> {
+    let mut all = identity_file()?.to_recipients()?;
+    for recipient in recipients {
+        let recipient: age::x25519::Recipient = recipient
+            .parse()
+            .map_err(|err| anyhow!("invalid recipient {}: {}", recipient, err))?;
+        all.push(Box::new(recipient));
+    }
+    let encryptor = Encryptor::with_recipients(all.iter().map(|r| r.as_ref() as &dyn Recipient))?;
+    armor(encryptor, plaintext)
+}
+
+/// Encrypts `plaintext` with a passphrase, returning an ASCII-armored age
+/// file.
+pub fn encrypt_with_passphrase(plaintext: &str, passphrase: &str) -> Result<String> {
+    armor(Encryptor::with_user_passphrase(SecretString::from(passphrase)), plaintext)
+}
+
+/// Encrypts `plaintext` to a single recipient, returning an ASCII-armored age
+/// file.
+pub fn encrypt_to(plaintext: &str, recipient: &dyn Recipient) -> Result<String> {
+    armor(Encryptor::with_recipients(iter::once(recipient))?, plaintext)
+}
+
+fn armor(encryptor: Encryptor, plaintext: &str) -> Result<String> {
+    let mut armored = Vec::new();
+    let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(&mut armored, Format::AsciiArmor)?)?;
+    writer.write_all(plaintext.as_bytes())?;
+    writer.finish()?.finish()?;
+    Ok(String::from_utf8(armored)?)
+}
@@ -1,16 +1,25 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
 
-use clap::Parser;
+use anyhow::{anyhow, bail, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{Parser, ValueEnum};
 use cli::Cli;
 use convert_case::Casing;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use toml::Value;
 
-use crate::config::Config;
+use crate::{cli::list::shell_quote, config::{Config, Verbosity}};
 
 mod cli;
 mod config;
+mod encryption;
 mod metadata;
+mod provider;
+mod vault;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Profile {
@@ -23,59 +32,665 @@ fn main() {
     cli.execute();
 }
 
+/// Name of the tables whose variables are secret.
+const SECRETS: &str = "secrets";
+
+/// Keys understood by a table that defines a single variable, such as
+/// `{ value = "...", secret = true }`, `{ age = "..." }`,
+/// `{ secret = "name" }`, `{ provider = "name", path = "..." }` or
+/// `{ file = "...", encoding = "base64", trim = true }`.
+const VALUE_KEYS: [&str; 8] = ["value", "secret", "age", "provider", "path", "file", "encoding", "trim"];
+
+/// Keys of `VALUE_KEYS` that give a variable its value, along with a string
+/// `secret`. A table defines a variable only if it has exactly one of them.
+const SOURCE_KEYS: [&str; 4] = ["value", "age", "provider", "file"];
+
+/// Replaces secret values in human-facing output.
+pub const MASK: &str = "********";
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub value: String,
+    pub secret: bool,
+}
+
+impl Variable {
+    /// Returns the value, or the mask if the variable is secret.
+    pub fn display(&self) -> &str {
+        if self.secret {
+            MASK
+        } else {
+            &self.value
+        }
+    }
+}
+
+/// Longest value shown in activation messages before it is truncated.
+const PREVIEW_LEN: usize = 40;
+
+/// Shortens a value for activation messages, masking it if secret.
+fn preview(value: &str, secret: bool) -> String {
+    if secret {
+        return MASK.to_string()
+    }
+    let value = value.replace('\n', "\\n");
+    if value.chars().count() > PREVIEW_LEN {
+        let mut value: String = value.chars().take(PREVIEW_LEN - 1).collect();
+        value.push('…');
+        return value
+    }
+    value
+}
+
+/// Reports an error and exits.
+pub fn fail(message: &str) -> ! {
+    eprintln!("rv: {}", message);
+    std::process::exit(1);
+}
+
+/// Returns the rv file of a directory: the first of the file names configured
+/// in config.toml that exists.
+pub fn find_rv(dir: &Path, config: &Config) -> Option<PathBuf> {
+    config.files
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+/// Returns the override file of an rv file, such as rv.local.toml for rv.toml.
+pub fn local_rv_path(rv_path: &Path) -> PathBuf {
+    let name = rv_path.file_name().unwrap().to_str().unwrap();
+    let local_name = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}.local.{}", stem, extension),
+        _ => format!("{}.local", name),
+    };
+    rv_path.with_file_name(local_name)
+}
+
+/// Reads an rv file and merges its override file over it, if any.
+fn load_rv(rv_path: &Path) -> Value {
+    let mut rv = read_rv(rv_path);
+    let local_path = local_rv_path(rv_path);
+    if local_path.exists() {
+        merge(&mut rv, read_rv(&local_path));
+    }
+    rv
+}
+
+/// Reads an rv file, parsed as YAML or JSON depending on its extension and as
+/// TOML otherwise.
+fn read_rv(rv_path: &Path) -> Value {
+    let rv_file = std::fs::read_to_string(rv_path).unwrap();
+    let rv = match rv_path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => serde_yaml::from_str(&rv_file).map_err(anyhow::Error::from),
+        Some("json") => serde_json::from_str(&rv_file).map_err(anyhow::Error::from),
+        _ => toml::from_str(&rv_file).map_err(anyhow::Error::from),
+    };
+    match rv {
+        Ok(rv) => rv,
+        Err(e) => fail(&format!("invalid {}: {}", rv_path.display(), e)),
+    }
+}
+
+/// Deep-merges `over` into `base`. Tables are merged key by key, while
+/// variables, including tables such as `{ age = "..." }`, are replaced.
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Table(base), Value::Table(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(inner) if inner.is_table() && value.is_table()
+                        && !is_variable(inner) && !is_variable(&value) => merge(inner, value),
+                    _ => {
+                        base.insert(key, value);
+                    },
+                }
+            }
+        },
+        (base, over) => *base = over,
+    }
+}
+
+/// Returns the path of the file defining global profiles.
+pub fn global_rv_path() -> PathBuf {
+    Config::dir().join("profiles.toml")
+}
+
+/// Collects the variables of a global profile. Returns `None` if the profile
+/// does not exist.
+fn global_profile_to_map(profile: &str, case: &Option<cli::list::Case>) -> Option<IndexMap<String, Variable>> {
+    let rv_path = global_rv_path();
+    if !rv_path.exists() {
+        return None
+    }
+    let rv = read_rv(&rv_path);
+    profile_to_map(&rv, rv_path.parent().unwrap(), profile, case)
+}
+
+thread_local! {
+    static EXTENDING: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static INPUTS: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Returns the `file` and `dotenv` paths resolved since the last call, so
+/// that changes to them can be detected without resolving the profiles.
+pub fn take_inputs() -> Vec<PathBuf> {
+    INPUTS.with_borrow_mut(std::mem::take)
+}
+
+fn add_input(path: &Path) {
+    INPUTS.with_borrow_mut(|inputs| {
+        if !inputs.iter().any(|input| input == path) {
+            inputs.push(path.to_path_buf());
+        }
+    });
+}
+
+/// Loads the global profiles listed in the `extends` setting of a table.
+fn load_extends(value: &Value, map: &mut IndexMap<String, Variable>) {
+    let Some(Value::Array(profiles)) = value.get("extends") else {
+        return
+    };
+    let profiles = profiles.iter().filter_map(Value::as_str);
+    for profile in profiles {
+        if EXTENDING.with_borrow(|extending| extending.iter().any(|p| p == profile)) {
+            eprintln!("rv: warning: global profile {} extends itself", profile);
+            continue
+        }
+        EXTENDING.with_borrow_mut(|extending| extending.push(profile.to_string()));
+        match global_profile_to_map(profile, &None) {
+            Some(global) => map.extend(global),
+            None => eprintln!("rv: warning: global profile {} not found", profile),
+        }
+        EXTENDING.with_borrow_mut(|extending| extending.pop());
+    }
+}
+
+/// Collects the variables of `profile`: variables of the global profiles
+/// listed in `extends` settings and of the `.env` files listed in `dotenv`
+/// settings first, then top-level variables of the rv.toml, then the
+/// variables of the profile table and its nested tables. Keys are
+/// converted with `case` if given, otherwise with the `case` setting of the
+/// file or profile. Returns `None` if the profile does not exist.
+fn profile_to_map(
+    rv: &Value,
+    dir: &Path,
+    profile: &str,
+    case: &Option<cli::list::Case>,
+) -> Option<IndexMap<String, Variable>> {
+    let mut base = IndexMap::new();
+    load_extends(rv, &mut base);
+    load_dotenv(rv, dir, &mut base);
+    let mut map = IndexMap::new();
+    let mut prefixed = is_prefixed(rv);
+    let mut table_case = case.clone().or_else(|| case_setting(rv));
+    if let Some(table) = rv.as_table() {
+        for (key, value) in table {
+            if is_setting(key, value) || has_conflicting_sources(key, value) || is_list(key, value) {
+                continue
+            }
+            if key == SECRETS {
+                rv_to_map(None, value, dir, &mut map, &table_case, false, true);
+            } else if is_variable(value) {
+                if let Some(variable) = to_variable(key, value, dir, false) {
+                    map.insert(convert_key(key.clone(), &table_case), variable);
+                }
+            }
+        }
+    }
+
+    let mut rv = rv;
+    for value in profile.split('.') {
+        rv = rv.get(value)?;
+        load_extends(rv, &mut base);
+        load_dotenv(rv, dir, &mut base);
+        prefixed |= is_prefixed(rv);
+        if case.is_none() {
+            table_case = case_setting(rv).or(table_case);
+        }
+    }
+    let case = &case.clone().or(table_case);
+
+    let mut profile_map = IndexMap::new();
+    rv_to_map(None, rv, dir, &mut profile_map, case, prefixed, false);
+    base.extend(map);
+    base.extend(profile_map);
+    Some(base)
+}
+
+/// Loads the `.env` files listed in the `dotenv` setting of a table, relative
+/// to `dir`. Files that do not exist are skipped.
+fn load_dotenv(value: &Value, dir: &Path, map: &mut IndexMap<String, Variable>) {
+    let Some(Value::Array(files)) = value.get("dotenv") else {
+        return
+    };
+    let files = files.iter().filter_map(Value::as_str);
+    for file in files {
+        let path = dir.join(file);
+        add_input(&path);
+        if !path.exists() {
+            continue
+        }
+        let iter = match dotenvy::from_path_iter(&path) {
+            Ok(iter) => iter,
+            Err(err) => {
+                eprintln!("rv: warning: could not read {}: {}", path.display(), err);
+                continue
+            },
+        };
+        for item in iter {
+            match item {
+                Ok((key, value)) => {
+                    map.insert(key, Variable {
+                        value,
+                        secret: false,
+                    });
+                },
+                Err(err) => {
+                    eprintln!("rv: warning: could not parse {}: {}", path.display(), err);
+                    break
+                },
+            }
+        }
+    }
+}
+
+/// Whether a key of an rv.toml table configures rv instead of defining a
+/// variable. Settings only count as such with the type they expect, so
+/// `prefix = "db"` is exported like any other string.
+fn is_setting(key: &str, value: &Value) -> bool {
+    match key {
+        "prefix" => value.is_bool(),
+        "case" => value
+            .as_str()
+            .is_some_and(|case| <cli::list::Case as ValueEnum>::from_str(case, true).is_ok()),
+        "recipients" | "dotenv" | "extends" => value.is_array(),
+        _ => false,
+    }
+}
+
+fn is_prefixed(value: &Value) -> bool {
+    value
+        .get("prefix")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Reads the `case` setting of a table, warning about unknown cases, which
+/// are exported as variables.
+fn case_setting(value: &Value) -> Option<cli::list::Case> {
+    let case = value.get("case")?.as_str()?;
+    match <cli::list::Case as ValueEnum>::from_str(case, true) {
+        Ok(case) => Some(case),
+        Err(_) => {
+            eprintln!("rv: warning: unknown case {}, exported as a variable", case);
+            None
+        },
+    }
+}
+
+fn convert_key(key: String, case: &Option<cli::list::Case>) -> String {
+    match case {
+        Some(case) => key.to_case(case.clone().into()),
+        None => key,
+    }
+}
+
+/// Whether a value defines a single variable: a scalar, or a table that only
+/// uses `VALUE_KEYS` and has exactly one of `SOURCE_KEYS`.
+fn is_variable(value: &Value) -> bool {
+    match value {
+        Value::Table(_) => value_sources(value) == Some(1),
+        value => scalar(value).is_some(),
+    }
+}
+
+/// Returns a scalar as a string, so that unquoted numbers and booleans, which
+/// are common in YAML and JSON, are exported as written.
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Integer(value) => Some(value.to_string()),
+        Value::Float(value) => Some(value.to_string()),
+        Value::Boolean(value) => Some(value.to_string()),
+        Value::Datetime(value) => Some(value.to_string()),
+        Value::Array(_) | Value::Table(_) => None,
+    }
+}
+
+/// Whether a value is a list, which cannot be exported and is reported.
+fn is_list(key: &str, value: &Value) -> bool {
+    if !value.is_array() {
+        return false
+    }
+    eprintln!("rv: warning: {} is a list, which cannot be exported, skipping it", key);
+    true
+}
+
+/// Counts the `SOURCE_KEYS` of a table that only uses `VALUE_KEYS`.
+fn value_sources(value: &Value) -> Option<usize> {
+    let table = value.as_table()?;
+    if !table.keys().all(|k| VALUE_KEYS.contains(&k.as_str())) {
+        return None
+    }
+    let sources = table
+        .keys()
+        .filter(|k| SOURCE_KEYS.contains(&k.as_str()))
+        .count();
+    Some(sources + usize::from(table.get("secret").is_some_and(Value::is_str)))
+}
+
+/// Whether a table looks like a variable but has more than one source, which
+/// is reported instead of being exported.
+fn has_conflicting_sources(key: &str, value: &Value) -> bool {
+    if value_sources(value).is_none_or(|sources| sources <= 1) {
+        return false
+    }
+    eprintln!("rv: warning: {} has more than one of value, age, provider, file and secret, skipping it", key);
+    true
+}
+
+/// Resolves the value of a variable, decrypting it or reading it from the
+/// vault, a provider or a file relative to `dir` if needed. Returns `None`
+/// and warns if the value cannot be resolved.
+fn to_variable(key: &str, value: &Value, dir: &Path, secret: bool) -> Option<Variable> {
+    if let Some(value) = scalar(value) {
+        return Some(Variable {
+            value,
+            secret,
+        })
+    }
+    let secret = secret || value
+        .get("secret")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if let Some(armored) = value.get("age").and_then(Value::as_str) {
+        return match encryption::decrypt(armored) {
+            Ok(value) => Some(Variable {
+                value,
+                secret: true,
+            }),
+            Err(err) => {
+                eprintln!("rv: warning: could not decrypt {}: {:#}", key, err);
+                None
+            },
+        }
+    }
+    if let Some(name) = value.get("provider").and_then(Value::as_str) {
+        let path = value
+            .get("path")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        return match provider::resolve(name, path) {
+            Ok(value) => Some(Variable {
+                value,
+                secret: true,
+            }),
+            Err(err) => {
+                eprintln!("rv: warning: could not resolve {}: {:#}", key, err);
+                None
+            },
+        }
+    }
+    if let Some(file) = value.get("file").and_then(Value::as_str) {
+        add_input(&dir.join(file));
+        return match read_file(&dir.join(file), value) {
+            Ok(value) => Some(Variable {
+                value,
+                secret,
+            }),
+            Err(err) => {
+                eprintln!("rv: warning: could not resolve {}: {:#}", key, err);
+                None
+            },
+        }
+    }
+    if let Some(name) = value.get("secret").and_then(Value::as_str) {
+        return match vault::resolve(name) {
+            Ok(value) => Some(Variable {
+                value,
+                secret: true,
+            }),
+            Err(err) => {
+                eprintln!("rv: warning: could not resolve {}: {:#}", key, err);
+                None
+            },
+        }
+    }
+    match value.get("value").and_then(Value::as_str) {
+        Some(value) => Some(Variable {
+            value: value.to_string(),
+            secret,
+        }),
+        None => {
+            eprintln!("rv: warning: {} has no value", key);
+            None
+        },
+    }
+}
+
+/// Reads the contents of a `{ file = "..." }` value, applying its
+/// `encoding` and `trim` options.
+fn read_file(path: &Path, value: &Value) -> anyhow::Result<String> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    let contents = match value.get("encoding").and_then(Value::as_str) {
+        Some("base64") => BASE64.encode(bytes),
+        Some(encoding) => bail!("unknown encoding {}", encoding),
+        None => String::from_utf8(bytes)
+            .map_err(|_| anyhow!("{} is not valid UTF-8, use encoding = \"base64\"", path.display()))?,
+    };
+    if value.get("trim").and_then(Value::as_bool).unwrap_or(false) {
+        return Ok(contents.trim().to_string())
+    }
+    Ok(contents)
+}
+
+/// Flattens a profile table into `map`. Once a table sets `prefix = true`,
+/// the names of its nested tables are prepended to their keys, so
+/// `[prod.db] host` becomes `db_host`. Variables in a `secrets` table are
+/// marked secret.
 fn rv_to_map(
-    key: Option<&String>,
-    value: &mut Value,
-    map: &mut HashMap<String, String>,
+    prefix: Option<&str>,
+    value: &Value,
+    dir: &Path,
+    map: &mut IndexMap<String, Variable>,
     case: &Option<cli::list::Case>,
+    prefixed: bool,
+    secret: bool,
 ) {
-    match value {
-        Value::Table(table) => {
-            for (key, value) in table {
-                rv_to_map(Some(key), value, map, case);
+    let Value::Table(table) = value else {
+        return
+    };
+    let prefixed = prefixed || is_prefixed(value);
+    for (key, value) in table {
+        if is_setting(key, value) {
+            continue
+        }
+        if key == SECRETS {
+            rv_to_map(prefix, value, dir, map, case, prefixed, true);
+            continue
+        }
+        let key = match prefix {
+            Some(prefix) => format!("{}_{}", prefix, key),
+            None => key.clone(),
+        };
+        if has_conflicting_sources(&key, value) || is_list(&key, value) {
+            continue
+        }
+        if is_variable(value) {
+            let key = convert_key(key, case);
+            if map.contains_key(&key) {
+                eprintln!("rv: warning: {} is defined more than once in the profile, using the last value", key);
             }
-        },
-        value => {
-            let value = value.as_str().unwrap();
-            let mut key = key.unwrap().clone();
-            if case.is_some() {
-                key = key.to_case(case.clone().unwrap().into());
+            if let Some(variable) = to_variable(&key, value, dir, secret) {
+                map.insert(key, variable);
             }
-            map.insert(key, value.to_string());
-        },
+        } else if value.is_table() {
+            rv_to_map(prefixed.then_some(key.as_str()), value, dir, map, case, prefixed, secret);
+        }
     }
 }
 
+/// Whether `key` can be exported by a POSIX shell, as in `[A-Za-z_][A-Za-z0-9_]*`.
+pub fn is_shell_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Variables exported or unset by an activation, for its message.
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Exports the variables of `map` that are missing from the environment or
+/// differ from it. Variables in `unset` are about to be unset and count as
+/// missing.
 fn parse_rv(
-    key: Option<&String>,
-    outer: &mut Value,
+    map: &IndexMap<String, Variable>,
+    unset: &[String],
     current_pwd: &mut Profile,
-    export_changed: &mut bool,
+    changes: &mut Changes,
     cmd: &mut String,
-    export: &mut String,
     config: &Config,
 ) {
-    match outer {
-        Value::Table(inner) => {
-            for (key, value) in inner {
-                parse_rv(Some(key), value, current_pwd, export_changed, cmd, export, config);
-            }
-        },
-        outer => {
-            let value = outer.as_str().unwrap();
-            let key = key.unwrap();
-            current_pwd.variables.as_mut().unwrap().push(key.clone());
-            if let Ok(val) = std::env::var(key) {
-                if val != value {
-                    *export_changed = true;
-                    cmd.push_str(format!("export {}={}\n", key, value).as_str());
-                    export.push_str(&config.changed.paint(key));
+    for (key, Variable { value, secret }) in map {
+        if !is_shell_name(key) {
+            eprintln!("rv: warning: {} is not a valid variable name, skipping it", key);
+            continue
+        }
+        current_pwd.variables.as_mut().unwrap().push(key.clone());
+        let current = std::env::var(key)
+            .ok()
+            .filter(|_| !unset.contains(key));
+        if let Some(val) = current {
+            if val != *value {
+                cmd.push_str(format!("export {}={}\n", key, shell_quote(value)).as_str());
+                if config.verbosity == Verbosity::Verbose {
+                    changes.changed.push(format!("{}: {} → {}", key, preview(&val, *secret), preview(value, *secret)));
+                } else {
+                    changes.changed.push(key.clone());
                 }
-            } else {
-                *export_changed = true;
-                cmd.push_str(format!("export {}={}\n", key, value).as_str());
-                export.push_str(&config.added.paint(key));
             }
-        },
+        } else {
+            cmd.push_str(format!("export {}={}\n", key, shell_quote(value)).as_str());
+            changes.added.push(key.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(rv: &str, profile: &str) -> Vec<(String, String)> {
+        values(&toml::from_str(rv).unwrap(), profile)
+    }
+
+    fn values(rv: &Value, profile: &str) -> Vec<(String, String)> {
+        profile_to_map(rv, Path::new("."), profile, &None)
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k, v.value))
+            .collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn settings_are_not_exported() {
+        let rv = r#"
+            [dev]
+            prefix = true
+            case = "upper"
+            recipients = []
+            dotenv = []
+            extends = []
+            a = "1"
+        "#;
+        assert_eq!(variables(rv, "dev"), pairs(&[("A", "1")]));
+    }
+
+    #[test]
+    fn settings_of_another_type_are_exported() {
+        let rv = r#"
+            [dev]
+            prefix = "db"
+            case = "nope"
+            recipients = "age1"
+            dotenv = ".env"
+            extends = "base"
+        "#;
+        assert_eq!(variables(rv, "dev"), pairs(&[
+            ("prefix", "db"),
+            ("case", "nope"),
+            ("recipients", "age1"),
+            ("dotenv", ".env"),
+            ("extends", "base"),
+        ]));
+    }
+
+    #[test]
+    fn tables_without_a_source_are_profiles() {
+        let rv = r#"
+            [dev.tools]
+            path = "/opt/bin"
+        "#;
+        assert_eq!(variables(rv, "dev"), pairs(&[("path", "/opt/bin")]));
+    }
+
+    #[test]
+    fn tables_with_several_sources_are_skipped() {
+        let rv = r#"
+            [dev]
+            a = "1"
+
+            [dev.db]
+            value = "1"
+            file = "cert.pem"
+        "#;
+        assert_eq!(variables(rv, "dev"), pairs(&[("a", "1")]));
+    }
+
+    #[test]
+    fn tables_with_one_source_are_variables() {
+        let rv = r#"
+            [dev]
+            a = { value = "1", secret = true }
+            b = { value = "2" }
+        "#;
+        assert_eq!(variables(rv, "dev"), pairs(&[("a", "1"), ("b", "2")]));
+    }
+
+    #[test]
+    fn shell_names() {
+        assert!(is_shell_name("DB_HOST"));
+        assert!(is_shell_name("_private1"));
+        assert!(!is_shell_name(""));
+        assert!(!is_shell_name("1PASSWORD"));
+        assert!(!is_shell_name("db-host"));
+        assert!(!is_shell_name("A;rm -rf ~"));
+        assert!(!is_shell_name("ÉTÉ"));
+    }
+
+    #[test]
+    fn scalars_are_exported_as_strings() {
+        let rv = serde_yaml::from_str("dev:\n  PORT: 5432\n  DEBUG: true\n  RATIO: 0.5\n  HOSTS: [a, b]\n").unwrap();
+        assert_eq!(values(&rv, "dev"), pairs(&[("PORT", "5432"), ("DEBUG", "true"), ("RATIO", "0.5")]));
+
+        let rv = "PORT = 5432\n[dev]\nSTARTED = 1979-05-27\n";
+        assert_eq!(variables(rv, "dev"), pairs(&[("PORT", "5432"), ("STARTED", "1979-05-27")]));
     }
 }
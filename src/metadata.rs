@@ -1,24 +1,290 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::Profile;
+use crate::{config::Config, global_rv_path, local_rv_path, Profile};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Identifies the shell session, exported by `rv precmd`.
+pub const SESSION_VAR: &str = "RV_SESSION";
+
+/// PID of the shell that owns the session, exported along with `SESSION_VAR`
+/// so that shells started from it, which inherit both, start their own.
+pub const SESSION_PID_VAR: &str = "RV_SESSION_PID";
+
+/// Fingerprint of the files the last activation was computed from.
+pub const FINGERPRINT_VAR: &str = "RV_FINGERPRINT";
+
+/// Sessions whose state has not changed for this long are removed.
+const SESSION_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Metadata {
+    /// Profile activated with `rv set --global`, independent of the directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub global: Option<Profile>,
+    /// Profiles by directory
     #[serde(flatten)]
     pub profiles: HashMap<PathBuf, Profile>,
+    /// Files the variables of the session were read from, other than the
+    /// profiles themselves
+    #[serde(skip)]
+    pub inputs: Vec<PathBuf>,
+    /// Variables of cleared profiles, unset at the next prompt
+    #[serde(skip)]
+    pub pending_unset: Vec<String>,
+    #[serde(skip)]
+    session: Option<Session>,
+}
+
+/// A shell session layered over the persistent metadata.
+#[derive(Debug)]
+struct Session {
+    id: String,
+    /// Profile names of the persistent metadata
+    defaults: HashMap<PathBuf, String>,
+    global_default: Option<String>,
+}
+
+/// State of a shell session. Profile names are only stored where they differ
+/// from the persistent defaults, while exported variables are always
+/// session-scoped.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SessionState {
+    /// Profiles set in this session, `None` if cleared
+    #[serde(default)]
+    names: HashMap<PathBuf, Option<String>>,
+    #[serde(default)]
+    global: Option<String>,
+    #[serde(default)]
+    global_cleared: bool,
+    #[serde(default)]
+    variables: HashMap<PathBuf, Vec<String>>,
+    #[serde(default)]
+    global_variables: Option<Vec<String>>,
+    #[serde(default)]
+    inputs: Vec<PathBuf>,
+    #[serde(default)]
+    pending_unset: Vec<String>,
+}
+
+/// Profiles used to be keyed by the path of their rv.toml rather than by
+/// their directory.
+fn migrate(path: PathBuf) -> PathBuf {
+    match path.file_name() {
+        Some(name) if name == "rv.toml" => path.parent().unwrap().to_path_buf(),
+        _ => path,
+    }
+}
+
+pub fn metadata_file() -> PathBuf {
+    Config::data_dir().join("metadata.json")
+}
+
+fn session_file(id: &str) -> PathBuf {
+    Config::data_dir().join("state").join(format!("{}.json", id))
 }
 
 impl Metadata {
+    /// Loads the persistent metadata, with the state of the current shell
+    /// session layered over it.
     pub fn load() -> Metadata {
-        let metadata_file = dirs::data_dir().unwrap().join("rv").join("metadata.json");
-        let metadata_str = std::fs::read_to_string(metadata_file).unwrap();
-        serde_json::from_str(&metadata_str).unwrap()
+        let mut metadata = Metadata::load_persistent();
+        let Ok(id) = std::env::var(SESSION_VAR) else {
+            return metadata
+        };
+        let mut state: SessionState = std::fs::read_to_string(session_file(&id))
+            .ok()
+            .and_then(|state| serde_json::from_str(&state).ok())
+            .unwrap_or_default();
+        state.names = state.names.into_iter().map(|(k, v)| (migrate(k), v)).collect();
+        state.variables = state.variables.into_iter().map(|(k, v)| (migrate(k), v)).collect();
+
+        let session = Session {
+            id,
+            defaults: metadata.profiles
+                .iter()
+                .map(|(path, profile)| (path.clone(), profile.name.clone()))
+                .collect(),
+            global_default: metadata.global.as_ref().map(|profile| profile.name.clone()),
+        };
+        for (path, name) in state.names {
+            match name {
+                Some(name) => {
+                    metadata.profiles.insert(path, Profile {
+                        name,
+                        variables: None,
+                    });
+                },
+                None => {
+                    metadata.profiles.remove(&path);
+                },
+            }
+        }
+        for (path, profile) in metadata.profiles.iter_mut() {
+            profile.variables = state.variables.get(path).cloned();
+        }
+        if state.global_cleared {
+            metadata.global = None;
+        } else if let Some(name) = state.global {
+            metadata.global = Some(Profile {
+                name,
+                variables: None,
+            });
+        }
+        if let Some(global) = metadata.global.as_mut() {
+            global.variables = state.global_variables;
+        }
+        metadata.inputs = state.inputs;
+        metadata.pending_unset = state.pending_unset;
+        metadata.session = Some(session);
+        metadata
     }
 
+    /// Returns the files `load` reads, including the state of the current
+    /// shell session and the inputs recorded in it.
+    pub fn files() -> Vec<PathBuf> {
+        let mut files = vec![metadata_file()];
+        if let Ok(id) = std::env::var(SESSION_VAR) {
+            let state: SessionState = std::fs::read_to_string(session_file(&id))
+                .ok()
+                .and_then(|state| serde_json::from_str(&state).ok())
+                .unwrap_or_default();
+            files.push(session_file(&id));
+            files.extend(state.inputs);
+        }
+        files
+    }
+
+    /// Loads the persistent metadata, which is empty until a profile is set.
+    fn load_persistent() -> Metadata {
+        let Ok(metadata_str) = std::fs::read_to_string(metadata_file()) else {
+            return Metadata::default()
+        };
+        let mut metadata: Metadata = serde_json::from_str(&metadata_str).unwrap();
+        metadata.profiles = metadata.profiles
+            .into_iter()
+            .map(|(path, profile)| (migrate(path), profile))
+            .collect();
+        metadata
+    }
+
+    /// Saves the state of the current shell session, or the persistent
+    /// metadata outside of a session.
     pub fn save(&self) {
-        let metadata_file = dirs::data_dir().unwrap().join("rv").join("metadata.json");
-        std::fs::write(metadata_file, serde_json::to_string(self).unwrap()).unwrap();
+        let Some(session) = &self.session else {
+            std::fs::create_dir_all(Config::data_dir()).unwrap();
+            std::fs::write(metadata_file(), serde_json::to_string(self).unwrap()).unwrap();
+            return
+        };
+
+        let mut state = SessionState::default();
+        for (path, profile) in &self.profiles {
+            if session.defaults.get(path) != Some(&profile.name) {
+                state.names.insert(path.clone(), Some(profile.name.clone()));
+            }
+            if let Some(variables) = &profile.variables {
+                state.variables.insert(path.clone(), variables.clone());
+            }
+        }
+        for path in session.defaults.keys() {
+            if !self.profiles.contains_key(path) {
+                state.names.insert(path.clone(), None);
+            }
+        }
+        match &self.global {
+            Some(global) => {
+                if session.global_default.as_ref() != Some(&global.name) {
+                    state.global = Some(global.name.clone());
+                }
+                state.global_variables = global.variables.clone();
+            },
+            None => state.global_cleared = session.global_default.is_some(),
+        }
+        state.inputs = self.inputs.clone();
+        state.pending_unset = self.pending_unset.clone();
+
+        let session_file = session_file(&session.id);
+        std::fs::create_dir_all(session_file.parent().unwrap()).unwrap();
+        std::fs::write(session_file, serde_json::to_string(&state).unwrap()).unwrap();
+    }
+
+    /// Persists `name` as the profile of `path` for every session, or as the
+    /// global profile if `path` is `None`.
+    pub fn set_default(&mut self, path: Option<&PathBuf>, name: &str) {
+        let mut persistent = Metadata::load_persistent();
+        let profile = match path {
+            Some(path) => persistent.profiles
+                .entry(path.clone())
+                .or_insert(Profile {
+                    name: name.to_string(),
+                    variables: None,
+                }),
+            None => persistent.global.get_or_insert(Profile {
+                name: name.to_string(),
+                variables: None,
+            }),
+        };
+        profile.name = name.to_string();
+        persistent.save();
+
+        if let Some(session) = self.session.as_mut() {
+            match path {
+                Some(path) => {
+                    session.defaults.insert(path.clone(), name.to_string());
+                },
+                None => session.global_default = Some(name.to_string()),
+            }
+        }
+    }
+
+    /// Starts a new shell session and returns its id, which the caller must
+    /// export as `SESSION_VAR`. Expired sessions are removed.
+    pub fn start_session() -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let id = format!("{:x}{:x}", now.as_nanos(), std::process::id());
+
+        let state_dir = session_file(&id).parent().unwrap().to_path_buf();
+        if let Ok(entries) = std::fs::read_dir(state_dir) {
+            for entry in entries.flatten() {
+                let expired = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .map(|m| m.elapsed().unwrap_or_default() > SESSION_EXPIRY)
+                    .unwrap_or(false);
+                if expired {
+                    std::fs::remove_file(entry.path()).ok();
+                }
+            }
+        }
+        id
+    }
+}
+
+/// Hashes the current directory, the shell session and the modification times
+/// of every file an activation is computed from, including the `file` and
+/// `dotenv` inputs of its profiles. Must be computed after the metadata is
+/// saved.
+pub fn fingerprint(current_dir: &Path, config: &Config) -> String {
+    let mut files = vec![global_rv_path(), Config::path()];
+    for name in &config.files {
+        let rv_path = current_dir.join(name);
+        files.push(local_rv_path(&rv_path));
+        files.push(rv_path);
+    }
+    files.extend(Metadata::files());
+
+    let mut hasher = DefaultHasher::new();
+    current_dir.hash(&mut hasher);
+    std::env::var(SESSION_VAR).ok().hash(&mut hasher);
+    for file in files {
+        let metadata = std::fs::metadata(&file).ok();
+        metadata.as_ref().and_then(|m| m.modified().ok()).hash(&mut hasher);
+        metadata.map(|m| m.len()).hash(&mut hasher);
     }
+    format!("{:016x}", hasher.finish())
 }
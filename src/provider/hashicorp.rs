@@ -0,0 +1,259 @@
+use std::{cell::RefCell, collections::HashMap, path::PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use ureq::{
+    tls::{Certificate, ClientCert, PrivateKey, RootCerts, TlsConfig},
+    Agent,
+};
+
+use super::{timeout, SecretProvider};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VaultConfig {
+    /// Server address, defaults to `$VAULT_ADDR`
+    pub address: Option<String>,
+    /// File containing the token, defaults to `$VAULT_TOKEN` or `~/.vault-token`
+    pub token_file: Option<PathBuf>,
+    /// Vault Enterprise namespace
+    pub namespace: Option<String>,
+    /// Version of the KV secrets engine
+    #[serde(default = "default_kv_version", deserialize_with = "deserialize_kv_version")]
+    pub kv_version: u8,
+    /// PEM file with the certificates trusted to verify the server
+    pub ca_cert: Option<PathBuf>,
+    /// PEM file with a client certificate for mutual TLS
+    pub client_cert: Option<PathBuf>,
+    /// PEM file with the key of `client_cert`
+    pub client_key: Option<PathBuf>,
+    /// Skips verification of the server certificate
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+fn default_kv_version() -> u8 {
+    2
+}
+
+fn deserialize_kv_version<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match u8::deserialize(deserializer)? {
+        version @ (1 | 2) => Ok(version),
+        version => Err(serde::de::Error::custom(format!("unknown kv_version {}, expected 1 or 2", version))),
+    }
+}
+
+/// Reads secrets from the KV secrets engine of a HashiCorp Vault compatible
+/// server. Paths have the form `mount/path#field`.
+pub struct HashicorpVault {
+    agent: Agent,
+    address: String,
+    token: String,
+    namespace: Option<String>,
+    kv_version: u8,
+    responses: RefCell<HashMap<String, Value>>,
+}
+
+impl HashicorpVault {
+    pub fn new(config: VaultConfig) -> Result<HashicorpVault> {
+        let address = match config.address {
+            Some(address) => address,
+            None => std::env::var("VAULT_ADDR").context("no address configured and VAULT_ADDR is not set")?,
+        };
+        let token = match (config.token_file, std::env::var("VAULT_TOKEN")) {
+            (Some(token_file), _) => std::fs::read_to_string(&token_file)
+                .with_context(|| format!("could not read {}", token_file.display()))?,
+            (None, Ok(token)) => token,
+            (None, Err(_)) => {
+                let token_file = dirs::home_dir().unwrap().join(".vault-token");
+                std::fs::read_to_string(&token_file)
+                    .with_context(|| format!("VAULT_TOKEN is not set and could not read {}", token_file.display()))?
+            },
+        };
+
+        let mut tls = TlsConfig::builder().disable_verification(config.insecure);
+        if let Some(ca_cert) = config.ca_cert {
+            let pem = std::fs::read(&ca_cert)
+                .with_context(|| format!("could not read {}", ca_cert.display()))?;
+            let certs = ureq::tls::parse_pem(&pem)
+                .filter_map(|item| match item {
+                    Ok(ureq::tls::PemItem::Certificate(cert)) => Some(cert),
+                    _ => None,
+                })
+                .collect::<Vec<Certificate>>();
+            tls = tls.root_certs(RootCerts::new_with_certs(&certs));
+        }
+        if let (Some(client_cert), Some(client_key)) = (config.client_cert, config.client_key) {
+            let cert = std::fs::read(&client_cert)
+                .with_context(|| format!("could not read {}", client_cert.display()))?;
+            let key = std::fs::read(&client_key)
+                .with_context(|| format!("could not read {}", client_key.display()))?;
+            tls = tls.client_cert(Some(ClientCert::new_with_certs(
+                &[Certificate::from_pem(&cert)?],
+                PrivateKey::from_pem(&key)?,
+            )));
+        }
+        let agent = Agent::config_builder()
+            .tls_config(tls.build())
+            .timeout_global(Some(timeout()))
+            .build()
+            .into();
+
+        Ok(HashicorpVault {
+            agent,
+            address: address.trim_end_matches('/').to_string(),
+            token: token.trim().to_string(),
+            namespace: config.namespace,
+            kv_version: config.kv_version,
+            responses: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the key-value pairs stored at `path`, fetching them once.
+    fn read(&self, path: &str) -> Result<Value> {
+        if let Some(data) = self.responses.borrow().get(path) {
+            return Ok(data.clone())
+        }
+        let url = match (self.kv_version, path.split_once('/')) {
+            (2, Some((mount, rest))) => format!("{}/v1/{}/data/{}", self.address, mount, rest),
+            (2, None) => bail!("{} does not include the mount of the secrets engine", path),
+            _ => format!("{}/v1/{}", self.address, path),
+        };
+        let mut request = self.agent
+            .get(&url)
+            .header("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response: Value = request
+            .call()
+            .with_context(|| format!("could not read {}", url))?
+            .body_mut()
+            .read_json()?;
+        let data = match self.kv_version {
+            2 => &response["data"]["data"],
+            _ => &response["data"],
+        };
+        if !data.is_object() {
+            bail!("unexpected response from {}", url)
+        }
+        self.responses.borrow_mut().insert(path.to_string(), data.clone());
+        Ok(data.clone())
+    }
+}
+
+impl SecretProvider for HashicorpVault {
+    fn get(&self, path: &str) -> Result<String> {
+        let (path, field) = path
+            .split_once('#')
+            .ok_or_else(|| anyhow!("{} does not name a field, as in kv/app#token", path))?;
+        match self.read(path)?.get(field) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => bail!("{} has no field {}", path, field),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+
+    use super::*;
+
+    /// Answers a single request with `status` and `body`, returning the
+    /// address of the server and its request line and headers.
+    fn serve(status: u16, body: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break
+                }
+                request.push_str(&line);
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body,
+            ).unwrap();
+            request
+        });
+        (address, handle)
+    }
+
+    fn vault(address: &str, kv_version: u8) -> HashicorpVault {
+        let token_file = std::env::temp_dir().join(format!("rv-test-token-{}", std::process::id()));
+        std::fs::write(&token_file, "s.token\n").unwrap();
+        let config: VaultConfig = toml::from_str(&format!(
+            "address = {:?}\ntoken_file = {:?}\nkv_version = {}",
+            address,
+            token_file,
+            kv_version,
+        )).unwrap();
+        HashicorpVault::new(config).unwrap()
+    }
+
+    #[test]
+    fn reads_kv_version_2() {
+        let (address, server) = serve(200, r#"{"data": {"data": {"token": "abc"}, "metadata": {}}}"#);
+        let value = vault(&address, 2).get("secret/app#token").unwrap();
+        let request = server.join().unwrap();
+        assert_eq!(value, "abc");
+        assert!(request.starts_with("GET /v1/secret/data/app HTTP/1.1\r\n"));
+        assert!(request.to_lowercase().contains("x-vault-token: s.token\r\n"));
+    }
+
+    #[test]
+    fn reads_kv_version_1() {
+        let (address, server) = serve(200, r#"{"data": {"token": "abc", "port": 5432}}"#);
+        let vault = vault(&address, 1);
+        assert_eq!(vault.get("kv/app#token").unwrap(), "abc");
+        assert_eq!(vault.get("kv/app#port").unwrap(), "5432");
+        assert!(server.join().unwrap().starts_with("GET /v1/kv/app HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let (address, server) = serve(200, r#"{"data": {"data": {"token": "abc"}}}"#);
+        let err = vault(&address, 2).get("secret/app#password").unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.to_string(), "secret/app has no field password");
+    }
+
+    #[test]
+    fn reports_http_errors() {
+        let (address, server) = serve(403, r#"{"errors": ["permission denied"]}"#);
+        let err = vault(&address, 2).get("secret/app#token").unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.to_string(), format!("could not read {}/v1/secret/data/app", address));
+    }
+
+    #[test]
+    fn rejects_unknown_kv_versions() {
+        let err = toml::from_str::<VaultConfig>("kv_version = 3").unwrap_err();
+        assert!(err.message().contains("unknown kv_version 3, expected 1 or 2"));
+        assert_eq!(toml::from_str::<VaultConfig>("").unwrap().kv_version, 2);
+    }
+
+    #[test]
+    fn requires_a_field() {
+        let err = vault("http://127.0.0.1:1", 2).get("secret/app").unwrap_err();
+        assert_eq!(err.to_string(), "secret/app does not name a field, as in kv/app#token");
+    }
+}
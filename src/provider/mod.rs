@@ -0,0 +1,76 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::config::Config;
+
+pub use self::hashicorp::{HashicorpVault, VaultConfig};
+
+mod hashicorp;
+
+/// Time allowed for a provider request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time allowed for a provider request while the prompt is waiting.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+static PROMPT: AtomicBool = AtomicBool::new(false);
+
+/// Shortens provider requests made before the shell can show its prompt.
+pub fn set_prompt() {
+    PROMPT.store(true, Ordering::Relaxed);
+}
+
+/// Returns the time allowed for a provider request.
+fn timeout() -> Duration {
+    match PROMPT.load(Ordering::Relaxed) {
+        true => PROMPT_TIMEOUT,
+        false => TIMEOUT,
+    }
+}
+
+/// An external store that resolves `{ provider = "name", path = "..." }`
+/// references.
+pub trait SecretProvider {
+    fn get(&self, path: &str) -> Result<String>;
+}
+
+/// A provider defined under `[providers.<name>]` in `config.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ProviderConfig {
+    /// HashiCorp Vault or a compatible server such as OpenBao
+    Vault(VaultConfig),
+}
+
+thread_local! {
+    static PROVIDERS: RefCell<HashMap<String, Rc<dyn SecretProvider>>> = RefCell::new(HashMap::new());
+}
+
+/// Resolves `path` with the provider configured as `name`. Providers are
+/// created once per process so they can reuse connections and responses.
+pub fn resolve(name: &str, path: &str) -> Result<String> {
+    let provider = PROVIDERS.with_borrow(|providers| providers.get(name).cloned());
+    let provider = match provider {
+        Some(provider) => provider,
+        None => {
+            let config = Config::load()
+                .providers
+                .shift_remove(name)
+                .ok_or_else(|| anyhow!("no provider named {} in config.toml", name))?;
+            let provider: Rc<dyn SecretProvider> = match config {
+                ProviderConfig::Vault(config) => Rc::new(HashicorpVault::new(config)?),
+            };
+            PROVIDERS.with_borrow_mut(|providers| providers.insert(name.to_string(), provider.clone()));
+            provider
+        },
+    };
+    provider.get(path)
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use age::{secrecy::ExposeSecret, x25519, Identity};
+use anyhow::{anyhow, bail, Context, Result};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, encryption, metadata::SESSION_VAR};
+
+static SECRETS: OnceLock<IndexMap<String, String>> = OnceLock::new();
+
+/// Unlocked vault contents, encrypted to the session identity.
+#[derive(Deserialize, Serialize)]
+struct Cache {
+    expires: u64,
+    modified: u64,
+    secrets: IndexMap<String, String>,
+}
+
+pub fn vault_path() -> PathBuf {
+    Config::data_dir().join("vault.age")
+}
+
+fn sessions_dir() -> PathBuf {
+    Config::data_dir().join("sessions")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn modified() -> u64 {
+    std::fs::metadata(vault_path())
+        .and_then(|m| m.modified())
+        .map(|m| m.duration_since(UNIX_EPOCH).unwrap().as_secs())
+        .unwrap_or(0)
+}
+
+/// Resolves a `{ secret = "name" }` reference, unlocking the vault if needed.
+pub fn resolve(name: &str) -> Result<String> {
+    unlock()?
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no secret named {} in the vault", name))
+}
+
+/// Returns the contents of the vault, from the session cache if possible and
+/// otherwise by asking for the passphrase.
+pub fn unlock() -> Result<&'static IndexMap<String, String>> {
+    if let Some(secrets) = SECRETS.get() {
+        return Ok(secrets)
+    }
+    let secrets = match read_cache() {
+        Some(secrets) => secrets,
+        None => {
+            let passphrase = prompt("rv vault passphrase: ")?;
+            let secrets = open(&passphrase)?;
+            write_cache(&secrets)?;
+            secrets
+        },
+    };
+    Ok(SECRETS.get_or_init(|| secrets))
+}
+
+/// Decrypts the vault with `passphrase`. A missing vault is empty.
+pub fn open(passphrase: &str) -> Result<IndexMap<String, String>> {
+    let armored = match std::fs::read_to_string(vault_path()) {
+        Ok(armored) => armored,
+        Err(_) => return Ok(IndexMap::new()),
+    };
+    let json = encryption::decrypt_with_passphrase(&armored, passphrase)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Encrypts `secrets` with `passphrase` and refreshes the cache of the
+/// current session.
+pub fn save(secrets: &IndexMap<String, String>, passphrase: &str) -> Result<()> {
+    let armored = encryption::encrypt_with_passphrase(&serde_json::to_string(secrets)?, passphrase)?;
+    let path = vault_path();
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(&path, armored)?;
+    if std::env::var(SESSION_VAR).is_ok() {
+        write_cache(secrets)?;
+    }
+    Ok(())
+}
+
+/// Asks for the passphrase of the vault, twice if the vault does not exist yet.
+pub fn prompt_passphrase() -> Result<String> {
+    if vault_path().exists() {
+        return prompt("rv vault passphrase: ")
+    }
+    let passphrase = prompt("new rv vault passphrase: ")?;
+    if passphrase != prompt("confirm passphrase: ")? {
+        bail!("passphrases do not match")
+    }
+    Ok(passphrase)
+}
+
+pub fn prompt(message: &str) -> Result<String> {
+    rpassword::prompt_password(message).context("could not read from the terminal")
+}
+
+/// The unlock cache of a shell session is encrypted to an identity that is
+/// kept next to it in a file only readable by the user, rather than in the
+/// environment where every child process would see it.
+fn identity_path(session: &str) -> PathBuf {
+    sessions_dir().join(format!("{}.key", session))
+}
+
+fn cache_path(session: &str) -> PathBuf {
+    sessions_dir().join(format!("{}.age", session))
+}
+
+fn session_identity(session: &str) -> Option<x25519::Identity> {
+    std::fs::read_to_string(identity_path(session)).ok()?.trim().parse().ok()
+}
+
+/// Writes a file that only the user can read.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+fn read_cache() -> Option<IndexMap<String, String>> {
+    let session = std::env::var(SESSION_VAR).ok()?;
+    let identity = session_identity(&session)?;
+    let path = cache_path(&session);
+    let armored = std::fs::read_to_string(&path).ok()?;
+    let json = encryption::decrypt_with(&armored, std::iter::once(&identity as &dyn Identity)).ok()?;
+    let cache: Cache = serde_json::from_str(&json).ok()?;
+    if cache.expires <= now() || cache.modified != modified() {
+        std::fs::remove_file(path).ok();
+        return None
+    }
+    Some(cache.secrets)
+}
+
+fn write_cache(secrets: &IndexMap<String, String>) -> Result<()> {
+    let Ok(session) = std::env::var(SESSION_VAR) else {
+        return Ok(())
+    };
+    let identity = session_identity(&session).unwrap_or_else(x25519::Identity::generate);
+    let timeout = Config::load().vault_timeout;
+    let cache = Cache {
+        expires: now() + timeout,
+        modified: modified(),
+        secrets: secrets.clone(),
+    };
+    let armored = encryption::encrypt_to(&serde_json::to_string(&cache)?, &identity.to_public())?;
+    std::fs::create_dir_all(sessions_dir())?;
+    remove_expired(Duration::from_secs(timeout));
+    write_private(&identity_path(&session), identity.to_string().expose_secret())?;
+    write_private(&cache_path(&session), &armored)?;
+    Ok(())
+}
+
+/// Removes caches of sessions that have not unlocked the vault for longer
+/// than the timeout.
+fn remove_expired(timeout: Duration) {
+    let Ok(entries) = std::fs::read_dir(sessions_dir()) else {
+        return
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map(|m| m.elapsed().unwrap_or_default() > timeout)
+            .unwrap_or(false);
+        if expired {
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+}
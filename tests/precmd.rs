@@ -0,0 +1,183 @@
+//! Runs `rv precmd` the way the prompt hook does, carrying its exports from
+//! one prompt to the next.
+
+use std::{collections::HashMap, path::PathBuf, process::Command};
+
+struct Shell {
+    root: PathBuf,
+    project: PathBuf,
+    env: HashMap<String, String>,
+}
+
+impl Shell {
+    /// Creates a project with `rv_toml` and the `dev` profile activated.
+    fn new(name: &str, rv_toml: &str) -> Shell {
+        let root = std::env::temp_dir().join(format!("rv-test-{}-{}", name, std::process::id()));
+        let project = root.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(root.join("data").join("rv")).unwrap();
+        std::fs::write(project.join("rv.toml"), rv_toml).unwrap();
+        let metadata = serde_json::json!({
+            project.to_str().unwrap(): { "name": "dev", "variables": null },
+        });
+        std::fs::write(root.join("data").join("rv").join("metadata.json"), metadata.to_string()).unwrap();
+        Shell {
+            root,
+            project,
+            env: HashMap::from([("RV_CHECK".to_string(), "1".to_string())]),
+        }
+    }
+
+    fn write(&self, file: &str, contents: &str) {
+        std::fs::write(self.project.join(file), contents).unwrap();
+    }
+
+    fn configure(&self, config: &str) {
+        let dir = self.root.join("config").join("rv");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), config).unwrap();
+    }
+
+    fn profiles(&self, profiles: &str) {
+        let dir = self.root.join("config").join("rv");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("profiles.toml"), profiles).unwrap();
+    }
+
+    /// Runs an rv command in the environment of the shell.
+    fn rv(&self, args: &[&str]) -> String {
+        let output = Command::new(env!("CARGO_BIN_EXE_rv"))
+            .args(args)
+            .current_dir(&self.project)
+            .env_remove("RV_SESSION")
+            .env_remove("RV_FINGERPRINT")
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("OLDPWD", &self.project)
+            .envs(&self.env)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs the prompt hook and applies its `export` and `unset` lines.
+    fn prompt(&mut self) -> String {
+        let output = self.rv(&["precmd"]);
+        for line in output.lines() {
+            if let Some((key, value)) = line.strip_prefix("export ").and_then(|l| l.split_once('=')) {
+                self.env.insert(key.to_string(), value.trim_matches('\'').to_string());
+            } else if let Some(key) = line.strip_prefix("unset ") {
+                self.env.remove(key);
+            }
+        }
+        output
+    }
+}
+
+impl Drop for Shell {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+#[test]
+fn unchanged_prompts_do_nothing() {
+    let mut shell = Shell::new("unchanged", "[dev]\nA = \"1\"\n");
+    assert!(shell.prompt().contains("export A=1\n"));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn file_changes_are_picked_up() {
+    let mut shell = Shell::new("file", "[dev]\nCERT = { file = \"cert.pem\" }\n");
+    shell.write("cert.pem", "one");
+    assert!(shell.prompt().contains("export CERT=one\n"));
+    assert_eq!(shell.prompt(), "");
+
+    shell.write("cert.pem", "two!");
+    assert!(shell.prompt().contains("export CERT='two!'\n"));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn dotenv_changes_are_picked_up() {
+    let mut shell = Shell::new("dotenv", "[dev]\ndotenv = [\".env\"]\nA = \"1\"\n");
+    assert!(shell.prompt().contains("export A=1\n"));
+    assert_eq!(shell.prompt(), "");
+
+    shell.write(".env", "B=2\n");
+    assert!(shell.prompt().contains("export B=2\n"));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn quiet_prompts_do_not_echo() {
+    let mut shell = Shell::new("normal", "[prod]\nA = \"1\"\n");
+    assert!(shell.prompt().contains("echo 'profile dev not found'\n"));
+
+    let mut shell = Shell::new("quiet", "[prod]\nA = \"1\"\n");
+    shell.configure("verbosity = \"quiet\"\n");
+    assert!(!shell.prompt().contains("echo"));
+}
+
+#[test]
+fn cleared_profiles_are_unset() {
+    let mut shell = Shell::new("clear", "[dev]\nA = \"1\"\n");
+    assert!(shell.prompt().contains("export A=1\n"));
+
+    shell.rv(&["clear"]);
+    assert!(shell.prompt().contains("unset A\n"));
+    assert!(!shell.env.contains_key("A"));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn cleared_global_profiles_are_unset() {
+    let mut shell = Shell::new("clear-global", "[dev]\nA = \"1\"\n");
+    shell.profiles("[aws]\nAWS_PROFILE = \"me\"\n");
+    shell.prompt();
+    shell.rv(&["set", "--global", "aws"]);
+    assert!(shell.prompt().contains("export AWS_PROFILE=me\n"));
+
+    shell.rv(&["clear", "--global"]);
+    let output = shell.prompt();
+    assert!(output.contains("unset AWS_PROFILE\n"));
+    assert!(!output.contains("unset A\n"));
+    assert_eq!(shell.env.get("A").map(String::as_str), Some("1"));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn missing_profile_notices_are_quoted() {
+    let mut shell = Shell::new("quoted", "[dev]\nA = \"1\"\n");
+    shell.prompt();
+    shell.rv(&["set", "--global", "it's"]);
+    let output = shell.prompt();
+    assert!(output.contains("echo 'global profile it'\\''s not found'\n"));
+    assert!(output.contains("export RV_FINGERPRINT="));
+}
+
+#[test]
+fn removed_profiles_are_unset() {
+    let mut shell = Shell::new("removed", "[dev]\nA = \"1\"\n");
+    assert!(shell.prompt().contains("export A=1\n"));
+
+    shell.write("rv.toml", "[prod]\nA = \"1\"\n");
+    let output = shell.prompt();
+    assert!(output.contains("echo 'profile dev not found'\n"));
+    assert!(output.contains("unset A\n"));
+    assert!(output.contains("export RV_FINGERPRINT="));
+    assert_eq!(shell.prompt(), "");
+}
+
+#[test]
+fn prompt_counts_global_variables() {
+    let mut shell = Shell::new("count", "[prod]\nA = \"1\"\n");
+    shell.configure("[templates]\nprompt = \"{profile}/{global}:{count}\"\n");
+    shell.profiles("[aws]\nAWS_PROFILE = \"me\"\nAWS_REGION = \"eu-west-1\"\n");
+    shell.rv(&["clear"]);
+    shell.rv(&["set", "--global", "aws"]);
+    shell.prompt();
+    assert_eq!(shell.rv(&["prompt", "--color", "never"]), "/aws:2\n");
+}
@@ -0,0 +1,110 @@
+//! Runs `rv set` and friends against data and config locations that do not
+//! exist yet, as chosen by the XDG directories, `RV_DATA_DIR`, `RV_CONFIG`
+//! and `--config`.
+
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+struct Dirs {
+    root: PathBuf,
+    project: PathBuf,
+}
+
+impl Dirs {
+    fn new(name: &str) -> Dirs {
+        let root = std::env::temp_dir().join(format!("rv-test-{}-{}", name, std::process::id()));
+        let project = root.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        Dirs { root, project }
+    }
+
+    /// Runs rv in the project with the XDG directories under the root.
+    fn rv(&self, args: &[&str], env: &[(&str, &Path)]) -> Output {
+        let output = Command::new(env!("CARGO_BIN_EXE_rv"))
+            .args(args)
+            .current_dir(&self.project)
+            .env_remove("RV_SESSION")
+            .env_remove("RV_CONFIG")
+            .env_remove("RV_DATA_DIR")
+            .env("XDG_DATA_HOME", self.root.join("data"))
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        output
+    }
+
+    fn stdout(&self, args: &[&str], env: &[(&str, &Path)]) -> String {
+        String::from_utf8(self.rv(args, env).stdout).unwrap()
+    }
+
+    fn profile(&self, metadata: &Path) -> serde_json::Value {
+        let metadata: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(metadata).unwrap()).unwrap();
+        metadata[self.project.to_str().unwrap()]["name"].clone()
+    }
+}
+
+impl Drop for Dirs {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+#[test]
+fn set_creates_the_data_dir() {
+    let dirs = Dirs::new("set");
+    dirs.rv(&["set", "dev"], &[]);
+    assert_eq!(dirs.profile(&dirs.root.join("data").join("rv").join("metadata.json")), "dev");
+}
+
+#[test]
+fn set_uses_rv_data_dir() {
+    let dirs = Dirs::new("set-data-dir");
+    let data_dir = dirs.root.join("state").join("rv");
+    dirs.rv(&["set", "dev"], &[("RV_DATA_DIR", &data_dir)]);
+    assert_eq!(dirs.profile(&data_dir.join("metadata.json")), "dev");
+    assert!(!dirs.root.join("data").exists());
+}
+
+#[test]
+fn config_is_read_from_rv_config_and_the_flag() {
+    let dirs = Dirs::new("config");
+    std::fs::write(dirs.project.join("env.toml"), "[dev]\nA = \"1\"\n").unwrap();
+    std::fs::write(dirs.project.join("rv.toml"), "[dev]\nA = \"default\"\n").unwrap();
+    let env_config = dirs.root.join("env.toml");
+    std::fs::write(&env_config, "files = [\"env.toml\"]\n").unwrap();
+    let flag_config = dirs.root.join("flag.toml");
+    std::fs::write(&flag_config, "files = [\"rv.toml\"]\n").unwrap();
+
+    let list = ["list", "--profile", "dev", "--format", "env"];
+    assert_eq!(dirs.stdout(&list, &[]), "A=default\n");
+    assert_eq!(dirs.stdout(&list, &[("RV_CONFIG", &env_config)]), "A=1\n");
+    let flag = [&["--config", flag_config.to_str().unwrap()], &list[..]].concat();
+    assert_eq!(dirs.stdout(&flag, &[("RV_CONFIG", &env_config)]), "A=default\n");
+}
+
+#[test]
+fn paths_follow_the_overrides() {
+    let dirs = Dirs::new("paths");
+    let config = dirs.root.join("etc").join("rv.toml");
+    let data_dir = dirs.root.join("state");
+    let paths = dirs.stdout(&["paths"], &[("RV_CONFIG", &config), ("RV_DATA_DIR", &data_dir)]);
+    let etc = dirs.root.join("etc");
+    assert_eq!(paths, format!(
+        "config    {}\nprofiles  {}\nidentity  {}\ndata      {}\nmetadata  {}\nvault     {}\n",
+        config.display(),
+        etc.join("profiles.toml").display(),
+        etc.join("identity.txt").display(),
+        data_dir.display(),
+        data_dir.join("metadata.json").display(),
+        data_dir.join("vault.age").display(),
+    ));
+
+    let flag = dirs.root.join("flag.toml");
+    let paths = dirs.stdout(&["--config", flag.to_str().unwrap(), "paths"], &[("RV_CONFIG", &config)]);
+    assert!(paths.starts_with(&format!("config    {}\nprofiles  {}\n", flag.display(), dirs.root.join("profiles.toml").display())));
+    assert!(paths.contains(&format!("data      {}\n", dirs.root.join("data").join("rv").display())));
+}